@@ -0,0 +1,90 @@
+// Measures how many queries per second the server answers while every forwarded
+// query takes `UPSTREAM_DELAY` to come back from the upstream resolver.
+//
+//   cargo bench --bench throughput
+
+use codecrafters_dns_server::Server;
+use std::net::{SocketAddr, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const UPSTREAM_DELAY: Duration = Duration::from_millis(20);
+const QUERIES: u16 = 200;
+
+fn main() {
+    let upstream = slow_upstream();
+
+    for workers in [1, 4, 16, 64] {
+        let addr = serve(upstream, workers);
+        let elapsed = run_queries(addr);
+        println!(
+            "workers={workers:>3}  {QUERIES} queries in {:>8.1?}  {:>8.1} queries/s",
+            elapsed,
+            QUERIES as f64 / elapsed.as_secs_f64()
+        );
+    }
+}
+
+// Answers every query with an empty response after a fixed delay, replying from a
+// separate thread so that queries do not wait for each other.
+fn slow_upstream() -> SocketAddr {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = socket.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let mut buf = [0u8; 4096];
+
+        while let Ok((size, from)) = socket.recv_from(&mut buf) {
+            let mut reply = buf[..size].to_vec();
+            reply[2] |= 0b10000000;
+            let socket = socket.try_clone().unwrap();
+
+            thread::spawn(move || {
+                thread::sleep(UPSTREAM_DELAY);
+                socket.send_to(&reply, from).unwrap();
+            });
+        }
+    });
+
+    addr
+}
+
+fn serve(upstream: SocketAddr, workers: usize) -> SocketAddr {
+    let addr = UdpSocket::bind("127.0.0.1:0")
+        .and_then(|socket| socket.local_addr())
+        .unwrap();
+    let server = Server::bind(addr)
+        .unwrap()
+        .resolver(vec![upstream])
+        .unwrap()
+        .workers(workers);
+
+    thread::spawn(move || server.run().unwrap());
+    thread::sleep(Duration::from_millis(50));
+    addr
+}
+
+fn run_queries(addr: SocketAddr) -> Duration {
+    let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+    client
+        .set_read_timeout(Some(Duration::from_secs(30)))
+        .unwrap();
+
+    let start = Instant::now();
+
+    for id in 0..QUERIES {
+        let query = [
+            &id.to_be_bytes()[..],
+            b"\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00\x00\x01\x00\x01",
+        ]
+        .concat();
+        client.send_to(&query, addr).unwrap();
+    }
+
+    let mut buf = [0u8; 512];
+    for _ in 0..QUERIES {
+        client.recv(&mut buf).unwrap();
+    }
+
+    start.elapsed()
+}
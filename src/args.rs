@@ -1,8 +1,70 @@
 use clap::Parser;
+use std::path::PathBuf;
 
 #[derive(Debug, Parser)]
 #[command(version, about, long_about = None)]
 pub struct Args {
+    /// Upstream resolver to forward queries to, repeat or separate with commas for failover
+    #[arg(long, value_delimiter = ',')]
+    pub resolver: Vec<String>,
+
+    /// Forward names under a suffix elsewhere, as `SUFFIX=ADDR[,ADDR...][/recurse|/refuse]`
+    #[arg(long)]
+    pub forward: Vec<String>,
+
+    /// File with one `--forward` rule per line, `#` starting a comment
+    #[arg(long)]
+    pub forward_file: Option<PathBuf>,
+
+    /// Serve a zone from an RFC 1035 master file, as `[ORIGIN=]FILE`
+    #[arg(long)]
+    pub zone: Vec<String>,
+
+    /// Root server to start recursion from instead of the built-in ones
+    #[arg(long, value_delimiter = ',')]
+    pub root_hint: Vec<String>,
+
+    /// Answer for CHAOS TXT `version.bind` and `version.server` queries
+    #[arg(long)]
+    pub identity_version: Option<String>,
+
+    /// Answer for CHAOS TXT `hostname.bind` and `id.server` queries
+    #[arg(long)]
+    pub identity_hostname: Option<String>,
+
+    /// Write full domain names in responses instead of compression pointers
+    #[arg(long)]
+    pub no_compression: bool,
+
+    /// Number of UDP queries resolved concurrently
+    #[arg(long, default_value_t = 16)]
+    pub workers: usize,
+
+    /// Randomize the letter case of forwarded query names (DNS 0x20)
+    #[arg(long)]
+    pub case_randomization: bool,
+
+    /// Maximum number of RRsets kept in the cache, 0 disables it
+    #[arg(long, default_value_t = 10_000)]
+    pub cache_size: usize,
+
+    /// Maximum number of NXDOMAIN and NODATA results kept in the cache, 0 disables them
+    #[arg(long, default_value_t = 2_000)]
+    pub negative_cache_size: usize,
+
+    /// Lowest TTL in seconds a cached RRset is kept for
+    #[arg(long, default_value_t = 0)]
+    pub cache_min_ttl: u32,
+
+    /// Highest TTL in seconds a cached RRset is kept for
+    #[arg(long, default_value_t = 86_400)]
+    pub cache_max_ttl: u32,
+
+    /// Seconds an expired cache entry is still served while it cannot be refreshed, 0 disables it
+    #[arg(long, default_value_t = 86_400)]
+    pub max_stale: u64,
+
+    /// Print cache hit and miss counters every this many seconds
     #[arg(long)]
-    pub resolver: Option<String>,
+    pub stats_interval: Option<u64>,
 }
@@ -5,6 +5,42 @@ pub enum Error {
     #[error("io -- {0}")]
     Io(#[from] std::io::Error),
 
+    #[error("parse -- {0}")]
+    Parse(#[from] ParseError),
+
     #[error("other -- {0}")]
     Other(#[from] anyhow::Error),
 }
+
+#[derive(Debug, ThisError)]
+pub enum ParseError {
+    #[error("name ends before its terminating label")]
+    UnexpectedEnd,
+
+    #[error("name is longer than 255 bytes")]
+    NameTooLong,
+
+    #[error("label of {0} bytes is longer than 63 bytes")]
+    LabelTooLong(usize),
+
+    #[error("name has an empty label")]
+    EmptyLabel,
+
+    #[error("name has an invalid escape sequence")]
+    InvalidEscape,
+
+    #[error("compression pointer at {at} does not point backwards to {to}")]
+    ForwardPointer { at: usize, to: usize },
+
+    #[error("name follows more than {0} compression pointers")]
+    TooManyPointers(usize),
+
+    #[error("reserved label type {0:#04x}")]
+    ReservedLabelType(u8),
+
+    #[error("OPT record is malformed")]
+    InvalidOpt,
+
+    #[error("message has more than one OPT record")]
+    MultipleOpt,
+}
@@ -7,11 +7,15 @@ mod message;
 mod resolver;
 mod server;
 mod utils;
+mod zone;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
 pub use args::Args;
-pub use error::Error;
+pub use error::{Error, ParseError};
 pub use server::Server;
 
-use message::{Answer, Message};
+use message::{
+    Answer, DomainName, Edns, EdnsOption, Message, OpCode, Question, Rcode, Rdata, RecordClass,
+    RecordType, MAX_CNAMES, MAX_UDP_PAYLOAD_SIZE,
+};
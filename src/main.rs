@@ -1,6 +1,7 @@
 use cds::{Args, Result, Server};
 use clap::Parser;
 use codecrafters_dns_server as cds;
+use std::time::Duration;
 
 fn main() {
     if let Err(err) = run() {
@@ -14,5 +15,21 @@ fn run() -> Result<()> {
 
     Server::bind("127.0.0.1:2053")?
         .resolver(args.resolver)?
+        .forward(args.forward)?
+        .forward_file(args.forward_file)?
+        .zones(args.zone)?
+        .root_hints(args.root_hint)?
+        .identity(args.identity_version, args.identity_hostname)
+        .compression(!args.no_compression)
+        .workers(args.workers)
+        .case_randomization(args.case_randomization)
+        .cache(
+            args.cache_size,
+            args.negative_cache_size,
+            args.cache_min_ttl,
+            args.cache_max_ttl,
+        )
+        .max_stale(Duration::from_secs(args.max_stale))
+        .stats_interval(args.stats_interval.map(Duration::from_secs))
         .run()
 }
@@ -1,12 +1,13 @@
-use super::{question::Question, DomainName, RecordType};
+use super::{question::Question, DomainName, Encoder, Rdata, RecordClass, RecordType};
 use crate::{utils, Result};
+use std::fmt;
 use std::io::Cursor;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Answer {
     name: DomainName,
     r#type: RecordType,
-    class: u16,
+    class: RecordClass,
     ttl: u32,
     data: Rdata,
 }
@@ -16,65 +17,172 @@ impl Answer {
         let name = DomainName::new(cursor)?;
         let bytes = utils::read_2_bytes(cursor)?;
         let r#type = RecordType::from_bytes(bytes);
-        let _ = utils::read_2_bytes(cursor)?;
+        let bytes = utils::read_2_bytes(cursor)?;
+        let class = RecordClass::from_bytes(bytes);
 
         let bytes = utils::read_4_bytes(cursor)?;
         let ttl = u32::from_be_bytes(bytes);
 
-        let _length = utils::read_2_bytes(cursor)?;
+        let bytes = utils::read_2_bytes(cursor)?;
+        let length = u16::from_be_bytes(bytes);
 
-        let bytes = utils::read_4_bytes(cursor)?;
-        let data = Rdata::A(bytes);
+        let data = Rdata::new(cursor, r#type, length)?;
 
         Ok(Self {
             name,
             r#type,
-            class: 1,
+            class,
             ttl,
             data,
         })
     }
 
-    pub fn as_bytes(&self) -> Vec<u8> {
-        self.name
-            .as_bytes()
-            .into_iter()
-            .chain(self.r#type.as_bytes())
-            .chain(self.class.to_be_bytes())
-            .chain(self.ttl.to_be_bytes())
-            .chain(self.data.len().to_be_bytes())
-            .chain(self.data.as_bytes())
-            .collect()
+    pub fn record(
+        name: DomainName,
+        r#type: RecordType,
+        class: RecordClass,
+        ttl: u32,
+        data: Rdata,
+    ) -> Self {
+        Self {
+            name,
+            r#type,
+            class,
+            ttl,
+            data,
+        }
+    }
+
+    // Text longer than a character-string is split over several; empty text still takes one.
+    // Ref: https://datatracker.ietf.org/doc/html/rfc1035#section-3.3.14
+    pub fn txt(q: &Question, ttl: u32, text: &[u8]) -> Self {
+        let mut strings: Vec<Vec<u8>> = text.chunks(u8::MAX as usize).map(<[u8]>::to_vec).collect();
+        if strings.is_empty() {
+            strings.push(vec![]);
+        }
+        let data = Rdata::Txt(strings);
+
+        Self::record(q.name().clone(), RecordType::Txt, q.class(), ttl, data)
+    }
+
+    pub fn set_name(self, name: DomainName) -> Self {
+        Self { name, ..self }
+    }
+
+    pub fn set_ttl(self, ttl: u32) -> Self {
+        Self { ttl, ..self }
+    }
+
+    pub fn name(&self) -> &DomainName {
+        &self.name
+    }
+
+    pub fn class(&self) -> RecordClass {
+        self.class
+    }
+
+    pub fn data(&self) -> &Rdata {
+        &self.data
+    }
+
+    pub fn r#type(&self) -> RecordType {
+        self.r#type
+    }
+
+    pub fn ttl(&self) -> u32 {
+        self.ttl
+    }
+
+    pub fn encode(&self, enc: &mut Encoder) {
+        enc.put_name(&self.name, true);
+        enc.put(&self.r#type.as_bytes());
+        enc.put(&self.class.as_bytes());
+        enc.put(&self.ttl.to_be_bytes());
+
+        // RDLENGTH is only known once RDATA, possibly compressed, has been written.
+        let pos = enc.position();
+        enc.put(&[0, 0]);
+        self.data.encode(enc);
+        let length = enc.position() - pos - 2;
+        enc.put_u16_at(pos, length as u16);
     }
 }
 
-#[derive(Debug)]
-enum Rdata {
-    A([u8; 4]),
+impl fmt::Display for Answer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}",
+            self.name, self.ttl, self.class, self.r#type, self.data
+        )
+    }
 }
 
-impl Rdata {
-    fn len(&self) -> u16 {
-        match self {
-            Self::A(_) => 4,
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn as_bytes(answer: &Answer) -> Vec<u8> {
+        let mut enc = Encoder::new(false);
+        answer.encode(&mut enc);
+        enc.into_bytes()
     }
 
-    fn as_bytes(&self) -> Vec<u8> {
-        match self {
-            Self::A(bytes) => bytes.to_vec(),
-        }
+    #[test]
+    fn it_decodes_compressed_names_in_rdata() {
+        // example.com MX 10 mail.<pointer to example.com>
+        let bytes: &[u8] = b"\x07example\x03com\x00\x00\x0f\x00\x01\x00\x00\x0e\x10\x00\x09\x00\x0a\x04mail\xc0\x00";
+        let mut cursor = Cursor::new(bytes);
+        let answer = Answer::new(&mut cursor).unwrap();
+
+        assert_eq!(cursor.position(), bytes.len() as u64);
+        assert_eq!(
+            as_bytes(&answer),
+            b"\x07example\x03com\x00\x00\x0f\x00\x01\x00\x00\x0e\x10\x00\x14\x00\x0a\x04mail\x07example\x03com\x00"
+        );
     }
-}
 
-impl From<&Question> for Answer {
-    fn from(q: &Question) -> Self {
-        Self {
-            name: q.name().clone(),
-            r#type: q.r#type(),
-            class: q.class(),
-            ttl: 60,
-            data: Rdata::A([8, 8, 8, 8]),
+    #[test]
+    fn it_reencodes_uncompressed_records_byte_exactly() {
+        let records: [&[u8]; 3] = [
+            b"\x03foo\x00\x00\x10\x00\x01\x00\x00\x00\x3c\x00\x0a\x05hello\x03abc",
+            b"\x03foo\x00\x00\x0d\x00\x01\x00\x00\x00\x3c\x00\x09\x03x86\x04unix",
+            b"\x03foo\x00\x00\x06\x00\x01\x00\x00\x00\x3c\x00\x1d\x02ns\x03foo\x00\x00\x00\x00\x00\x01\x00\x00\x00\x02\x00\x00\x00\x03\x00\x00\x00\x04\x00\x00\x00\x05",
+        ];
+
+        for bytes in records {
+            let answer = Answer::new(&mut Cursor::new(bytes)).unwrap();
+            assert_eq!(as_bytes(&answer), bytes);
         }
     }
+
+    #[test]
+    fn it_keeps_unknown_rdata_opaque() {
+        let bytes: &[u8] = b"\x03foo\x00\x30\x39\x00\x01\x00\x00\x00\x3c\x00\x03\xc0\x00\x01";
+        let answer = Answer::new(&mut Cursor::new(bytes)).unwrap();
+
+        assert_eq!(as_bytes(&answer), bytes);
+        assert_eq!(answer.to_string(), "foo. 60 IN TYPE12345 \\# 3 c00001");
+    }
+
+    #[test]
+    fn it_splits_txt_into_character_strings() {
+        let q = Question::from_parts(
+            "id.server".parse().unwrap(),
+            RecordType::Txt,
+            RecordClass::Ch,
+        );
+
+        let answer = Answer::txt(&q, 0, &[b'x'; 300]);
+        assert!(matches!(answer.data(), Rdata::Txt(strings) if strings.len() == 2));
+        let answer = Answer::txt(&q, 0, b"");
+        assert!(matches!(answer.data(), Rdata::Txt(strings) if strings == &[Vec::<u8>::new()]));
+        assert!(as_bytes(&answer).ends_with(b"\x00\x01\x00"));
+    }
+
+    #[test]
+    fn it_rejects_rdata_not_matching_its_length() {
+        let bytes: &[u8] = b"\x03foo\x00\x00\x05\x00\x01\x00\x00\x00\x3c\x00\x03\x03bar\x00";
+        assert!(Answer::new(&mut Cursor::new(bytes)).is_err());
+    }
 }
@@ -0,0 +1,106 @@
+use super::{Answer, DomainName, Encoder, Rcode, Rdata, RecordType};
+use crate::{ParseError, Result};
+
+// The largest payload this server advertises and accepts over UDP.
+pub const MAX_UDP_PAYLOAD_SIZE: u16 = 4096;
+// Without EDNS, UDP messages are limited to 512 bytes.
+pub const MIN_UDP_PAYLOAD_SIZE: u16 = 512;
+
+const DNSSEC_OK: u32 = 0x00008000;
+
+// OPT pseudo-record of the additional section.
+// Ref: https://datatracker.ietf.org/doc/html/rfc6891#section-6.1
+#[derive(Debug, Clone)]
+pub struct Edns {
+    udp_size: u16,
+    version: u8,
+    dnssec_ok: bool,
+    options: Vec<EdnsOption>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EdnsOption {
+    pub code: u16,
+    pub data: Vec<u8>,
+}
+
+impl EdnsOption {
+    // Ref: https://datatracker.ietf.org/doc/html/rfc5001
+    pub const NSID: u16 = 3;
+}
+
+impl Edns {
+    pub fn new(udp_size: u16) -> Self {
+        Self {
+            udp_size,
+            version: 0,
+            dnssec_ok: false,
+            options: vec![],
+        }
+    }
+
+    pub fn set_dnssec_ok(self, dnssec_ok: bool) -> Self {
+        Self { dnssec_ok, ..self }
+    }
+
+    pub fn set_option(self, option: EdnsOption) -> Self {
+        let mut options = self.options;
+        options.push(option);
+        Self { options, ..self }
+    }
+
+    pub fn udp_size(&self) -> u16 {
+        self.udp_size
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn dnssec_ok(&self) -> bool {
+        self.dnssec_ok
+    }
+
+    pub fn options(&self) -> &[EdnsOption] {
+        &self.options
+    }
+
+    // The extended RCODE bits are taken from the header when the record is written.
+    pub fn encode(&self, enc: &mut Encoder, rcode: Rcode) {
+        let flags = if self.dnssec_ok { DNSSEC_OK } else { 0 };
+        let ttl = (rcode.extended_bits() as u32) << 24 | (self.version as u32) << 16 | flags;
+
+        enc.put_name(&DomainName::root(), false);
+        enc.put(&RecordType::Opt.as_bytes());
+        enc.put(&self.udp_size.to_be_bytes());
+        enc.put(&ttl.to_be_bytes());
+
+        let pos = enc.position();
+        enc.put(&[0, 0]);
+        Rdata::encode_options(&self.options, enc);
+        let length = enc.position() - pos - 2;
+        enc.put_u16_at(pos, length as u16);
+    }
+}
+
+impl TryFrom<Answer> for Edns {
+    type Error = crate::Error;
+
+    fn try_from(a: Answer) -> Result<Self> {
+        let options = match a.data() {
+            Rdata::Opt(options) => options.clone(),
+            _ => return Err(ParseError::InvalidOpt.into()),
+        };
+
+        if !a.name().is_root() {
+            return Err(ParseError::InvalidOpt.into());
+        }
+
+        Ok(Self {
+            udp_size: a.class().as_u16(),
+            version: (a.ttl() >> 16) as u8,
+            dnssec_ok: a.ttl() & DNSSEC_OK != 0,
+            options,
+        })
+    }
+}
@@ -0,0 +1,76 @@
+use super::{name::label_part, DomainName};
+use std::collections::HashMap;
+
+// A compression pointer has 14 bits for its offset.
+const MAX_POINTER_OFFSET: usize = 0x3fff;
+
+// Serializes a message while remembering where each domain name suffix was written,
+// so that later occurrences can be replaced with a pointer.
+// Ref: https://datatracker.ietf.org/doc/html/rfc1035#section-4.1.4
+#[derive(Debug)]
+pub struct Encoder {
+    buf: Vec<u8>,
+    compress: bool,
+    names: HashMap<Vec<Vec<u8>>, u16>,
+}
+
+impl Encoder {
+    pub fn new(compress: bool) -> Self {
+        Self {
+            buf: vec![],
+            compress,
+            names: HashMap::new(),
+        }
+    }
+
+    pub fn put(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub fn put_name(&mut self, name: &DomainName, compressible: bool) {
+        if !(self.compress && compressible) {
+            self.put(&name.as_bytes());
+            return;
+        }
+
+        let labels = name.labels();
+
+        for i in 0..labels.len() {
+            let suffix: Vec<Vec<u8>> = labels[i..]
+                .iter()
+                .map(|label| label.to_ascii_lowercase())
+                .collect();
+
+            if let Some(offset) = self.names.get(&suffix) {
+                self.put(&(0xc000 | offset).to_be_bytes());
+                return;
+            }
+
+            if self.buf.len() <= MAX_POINTER_OFFSET {
+                self.names.insert(suffix, self.buf.len() as u16);
+            }
+
+            self.put(&label_part(&labels[i]));
+        }
+
+        self.put(&[0]);
+    }
+
+    pub fn position(&self) -> usize {
+        self.buf.len()
+    }
+
+    // Drops everything written from `pos` on, along with the names it could point to.
+    pub fn rewind(&mut self, pos: usize) {
+        self.buf.truncate(pos);
+        self.names.retain(|_, offset| (*offset as usize) < pos);
+    }
+
+    pub fn put_u16_at(&mut self, pos: usize, value: u16) {
+        self.buf[pos..pos + 2].copy_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
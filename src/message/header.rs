@@ -65,25 +65,6 @@ impl Header {
         }
     }
 
-    pub fn error() -> Self {
-        Self {
-            id: TransactionId(0),
-            qr: Qr::Reply,
-            opcode: OpCode::Query,
-            aa: AuthAnswer(false),
-            tc: Truncation(false),
-            rd: RecursionDesired(false),
-            ra: RecursionAvailable(false),
-            ad: AuthenticData(false),
-            cd: CheckingDisable(false),
-            rcode: Rcode::ServerErr,
-            num_of_qs: 0,
-            num_of_an: 0,
-            num_of_authorities: 0,
-            num_of_additionals: 0,
-        }
-    }
-
     pub fn set_qs(self, qs: u16) -> Self {
         Self {
             num_of_qs: qs,
@@ -98,9 +79,112 @@ impl Header {
         }
     }
 
+    pub fn set_ns(self, ns: u16) -> Self {
+        Self {
+            num_of_authorities: ns,
+            ..self
+        }
+    }
+
+    pub fn set_ar(self, ar: u16) -> Self {
+        Self {
+            num_of_additionals: ar,
+            ..self
+        }
+    }
+
+    pub fn set_tc(self, tc: bool) -> Self {
+        Self {
+            tc: Truncation(tc),
+            ..self
+        }
+    }
+
+    pub fn set_aa(self, aa: bool) -> Self {
+        Self {
+            aa: AuthAnswer(aa),
+            ..self
+        }
+    }
+
+    pub fn set_rd(self, rd: bool) -> Self {
+        Self {
+            rd: RecursionDesired(rd),
+            ..self
+        }
+    }
+
+    pub fn set_ra(self, ra: bool) -> Self {
+        Self {
+            ra: RecursionAvailable(ra),
+            ..self
+        }
+    }
+
+    pub fn set_ad(self, ad: bool) -> Self {
+        Self {
+            ad: AuthenticData(ad),
+            ..self
+        }
+    }
+
+    pub fn set_cd(self, cd: bool) -> Self {
+        Self {
+            cd: CheckingDisable(cd),
+            ..self
+        }
+    }
+
+    pub fn set_rcode(self, rcode: Rcode) -> Self {
+        Self { rcode, ..self }
+    }
+
+    pub fn extend_rcode(self, high: u8) -> Self {
+        Self {
+            rcode: self.rcode.extend(high),
+            ..self
+        }
+    }
+
     pub fn id(&self) -> TransactionId {
         self.id
     }
+
+    pub fn opcode(&self) -> OpCode {
+        self.opcode
+    }
+
+    pub fn rcode(&self) -> Rcode {
+        self.rcode
+    }
+
+    pub fn is_response(&self) -> bool {
+        matches!(self.qr, Qr::Reply)
+    }
+
+    pub fn tc(&self) -> bool {
+        self.tc.0
+    }
+
+    pub fn aa(&self) -> bool {
+        self.aa.0
+    }
+
+    pub fn rd(&self) -> bool {
+        self.rd.0
+    }
+
+    pub fn ra(&self) -> bool {
+        self.ra.0
+    }
+
+    pub fn ad(&self) -> bool {
+        self.ad.0
+    }
+
+    pub fn cd(&self) -> bool {
+        self.cd.0
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -139,11 +223,15 @@ impl Qr {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+// Ref: https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-5
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OpCode {
     Query,
     Iquery,
     Status,
+    Notify,
+    Update,
+    Dso,
     Unknown(u8),
 }
 
@@ -153,6 +241,9 @@ impl OpCode {
             Self::Query => 0b00000000,
             Self::Iquery => 0b00001000,
             Self::Status => 0b00010000,
+            Self::Notify => 0b00100000,
+            Self::Update => 0b00101000,
+            Self::Dso => 0b00110000,
             Self::Unknown(bits) => *bits,
         }
     }
@@ -163,40 +254,112 @@ impl OpCode {
             0b00000000 => Self::Query,
             0b00001000 => Self::Iquery,
             0b00010000 => Self::Status,
+            0b00100000 => Self::Notify,
+            0b00101000 => Self::Update,
+            0b00110000 => Self::Dso,
             _ => Self::Unknown(bits),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+// The header only carries the lower 4 bits of an RCODE. The upper 8 bits travel in the
+// TTL field of the EDNS OPT record.
+// Ref: https://datatracker.ietf.org/doc/html/rfc6895#section-2.3
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Rcode {
     NoErr,
     FormatErr,
     ServerErr,
     NonexistentDomain,
     NotImplemented,
+    Refused,
+    YxDomain,
+    YxRrset,
+    NxRrset,
+    NotAuth,
+    NotZone,
+    DsoTypeNotImplemented,
+    // BADVERS in OPT records, BADSIG in TSIG records
+    BadVers,
+    BadKey,
+    BadTime,
+    BadMode,
+    BadName,
+    BadAlg,
+    BadTrunc,
+    BadCookie,
+    Unknown(u16),
 }
 
 impl Rcode {
-    fn as_byte(&self) -> u8 {
+    pub fn as_u16(&self) -> u16 {
         match self {
-            Self::NoErr => 0b00000000,
-            Self::FormatErr => 0b00000001,
-            Self::ServerErr => 0b00000010,
-            Self::NonexistentDomain => 0b00000011,
-            Self::NotImplemented => 0b00000100,
+            Self::NoErr => 0,
+            Self::FormatErr => 1,
+            Self::ServerErr => 2,
+            Self::NonexistentDomain => 3,
+            Self::NotImplemented => 4,
+            Self::Refused => 5,
+            Self::YxDomain => 6,
+            Self::YxRrset => 7,
+            Self::NxRrset => 8,
+            Self::NotAuth => 9,
+            Self::NotZone => 10,
+            Self::DsoTypeNotImplemented => 11,
+            Self::BadVers => 16,
+            Self::BadKey => 17,
+            Self::BadTime => 18,
+            Self::BadMode => 19,
+            Self::BadName => 20,
+            Self::BadAlg => 21,
+            Self::BadTrunc => 22,
+            Self::BadCookie => 23,
+            Self::Unknown(code) => *code,
         }
     }
 
-    fn from_byte(byte: u8) -> Self {
-        match byte & 0b00001111 {
-            0b00000000 => Self::NoErr,
-            0b00000001 => Self::FormatErr,
-            0b00000010 => Self::ServerErr,
-            0b00000011 => Self::NonexistentDomain,
-            _ => Self::NotImplemented,
+    pub fn from_u16(code: u16) -> Self {
+        match code {
+            0 => Self::NoErr,
+            1 => Self::FormatErr,
+            2 => Self::ServerErr,
+            3 => Self::NonexistentDomain,
+            4 => Self::NotImplemented,
+            5 => Self::Refused,
+            6 => Self::YxDomain,
+            7 => Self::YxRrset,
+            8 => Self::NxRrset,
+            9 => Self::NotAuth,
+            10 => Self::NotZone,
+            11 => Self::DsoTypeNotImplemented,
+            16 => Self::BadVers,
+            17 => Self::BadKey,
+            18 => Self::BadTime,
+            19 => Self::BadMode,
+            20 => Self::BadName,
+            21 => Self::BadAlg,
+            22 => Self::BadTrunc,
+            23 => Self::BadCookie,
+            _ => Self::Unknown(code),
         }
     }
+
+    fn as_byte(&self) -> u8 {
+        (self.as_u16() & 0b00001111) as u8
+    }
+
+    fn from_byte(byte: u8) -> Self {
+        Self::from_u16((byte & 0b00001111) as u16)
+    }
+
+    // Combines the header bits with the extended bits of an OPT record.
+    pub fn extend(&self, high: u8) -> Self {
+        Self::from_u16(((high as u16) << 4) | (self.as_u16() & 0b00001111))
+    }
+
+    pub fn extended_bits(&self) -> u8 {
+        (self.as_u16() >> 4) as u8
+    }
 }
 
 // Authoritative Answer (AA)
@@ -388,6 +551,14 @@ impl Header {
         self.num_of_an
     }
 
+    pub fn num_of_authorities(&self) -> u16 {
+        self.num_of_authorities
+    }
+
+    pub fn num_of_additionals(&self) -> u16 {
+        self.num_of_additionals
+    }
+
     pub fn as_bytes(&self) -> [u8; 12] {
         let Self {
             id,
@@ -421,3 +592,44 @@ impl Header {
 fn bit_flag(mask: u8, byte: u8) -> bool {
     byte & mask == mask
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_round_trips_every_opcode_and_rcode() {
+        for opcode in 0..16u8 {
+            for rcode in 0..16u8 {
+                let bytes = [
+                    0x12,
+                    0x34,
+                    0x80 | opcode << 3,
+                    0x80 | rcode,
+                    0,
+                    1,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                ];
+                let header = Header::new(&mut &bytes[..]).unwrap();
+                assert_eq!(header.as_bytes(), bytes);
+            }
+        }
+
+        let header =
+            Header::new(&mut &b"\x00\x00\x28\x05\x00\x00\x00\x00\x00\x00\x00\x00"[..]).unwrap();
+        assert_eq!(header.opcode, OpCode::Update);
+        assert_eq!(header.rcode, Rcode::Refused);
+    }
+
+    #[test]
+    fn it_extends_rcode_with_opt_bits() {
+        assert_eq!(Rcode::NoErr.extend(1), Rcode::BadVers);
+        assert_eq!(Rcode::Refused.extend(0), Rcode::Refused);
+        assert_eq!(Rcode::from_u16(4095).extend(0), Rcode::Unknown(15));
+    }
+}
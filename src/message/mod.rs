@@ -1,19 +1,38 @@
-use crate::{utils, Error, Result};
-use std::io::{Cursor, Read, Seek, SeekFrom};
+use crate::{Error, ParseError, Result};
+use std::fmt;
+use std::io::Cursor;
+use std::str::FromStr;
 
 mod answer;
+mod edns;
+mod encoder;
 mod header;
+mod name;
 mod question;
+mod rdata;
 
 pub use answer::Answer;
-pub use header::Header;
+pub use edns::{Edns, EdnsOption, MAX_UDP_PAYLOAD_SIZE, MIN_UDP_PAYLOAD_SIZE};
+pub use encoder::Encoder;
+pub use header::{Header, OpCode, Rcode};
+pub use name::DomainName;
 pub use question::Question;
+pub use rdata::Rdata;
+
+// CNAMEs followed for one question before giving up on the chain, whether it is resolved,
+// cached or served from a zone.
+pub const MAX_CNAMES: usize = 8;
 
 #[derive(Debug)]
 pub struct Message {
     pub header: Header,
     pub questions: Vec<Question>,
     pub answers: Vec<Answer>,
+    pub authorities: Vec<Answer>,
+    pub additionals: Vec<Answer>,
+    pub edns: Option<Edns>,
+    // Largest UDP message the requestor accepts, not part of the wire format.
+    udp_payload_size: u16,
 }
 
 impl Message {
@@ -22,20 +41,37 @@ impl Message {
             header,
             questions: vec![],
             answers: vec![],
+            authorities: vec![],
+            additionals: vec![],
+            edns: None,
+            udp_payload_size: MIN_UDP_PAYLOAD_SIZE,
         }
     }
 
     pub fn reply(msg: Self) -> Self {
         let Self {
-            header, questions, ..
+            header,
+            questions,
+            edns,
+            ..
         } = msg;
 
-        let mut msg = Self::new(Header::new_reply(header));
+        let mut msg = Self {
+            udp_payload_size: negotiated_payload_size(edns.as_ref()),
+            ..Self::new(Header::new_reply(header))
+        };
 
         for q in questions {
             msg = msg.set_question(q);
         }
 
+        // An EDNS aware requestor gets our own OPT record back.
+        // Ref: https://datatracker.ietf.org/doc/html/rfc6891#section-7
+        if let Some(edns) = edns {
+            let reply_edns = Edns::new(MAX_UDP_PAYLOAD_SIZE).set_dnssec_ok(edns.dnssec_ok());
+            msg = msg.set_edns(reply_edns);
+        }
+
         msg
     }
 
@@ -45,11 +81,30 @@ impl Message {
         msg
     }
 
-    pub fn error() -> Self {
+    // Keeps whatever could be read from a message that failed to parse, so that an
+    // error reply still carries the client's ID and question.
+    pub fn salvage(bytes: &[u8]) -> Option<Self> {
+        let mut cursor = Cursor::new(bytes);
+        let header = Header::new(&mut cursor).ok()?;
+
+        let mut questions: Vec<Question> = vec![];
+        for _ in 0..header.num_of_qs() {
+            match Question::new(&mut cursor) {
+                Ok(q) => questions.push(q),
+                Err(_) => break,
+            }
+        }
+
+        Some(Self {
+            questions,
+            ..Self::new(header)
+        })
+    }
+
+    pub fn set_rcode(self, rcode: Rcode) -> Self {
         Self {
-            header: Header::error(),
-            questions: vec![],
-            answers: vec![],
+            header: self.header.set_rcode(rcode),
+            ..self
         }
     }
 
@@ -77,13 +132,126 @@ impl Message {
         }
     }
 
+    pub fn set_authority(self, a: Answer) -> Self {
+        let mut authorities = self.authorities;
+        authorities.push(a);
+        let header = self.header.set_ns(authorities.len() as u16);
+
+        Self {
+            header,
+            authorities,
+            ..self
+        }
+    }
+
+    pub fn set_additional(self, a: Answer) -> Self {
+        let mut additionals = self.additionals;
+        additionals.push(a);
+        let count = additionals.len() + self.edns.is_some() as usize;
+        let header = self.header.set_ar(count as u16);
+
+        Self {
+            header,
+            additionals,
+            ..self
+        }
+    }
+
+    pub fn set_edns(self, edns: Edns) -> Self {
+        let header = self.header.set_ar(self.additionals.len() as u16 + 1);
+
+        Self {
+            header,
+            edns: Some(edns),
+            ..self
+        }
+    }
+
+    // Keeps as many whole RRsets as fit within `limit` bytes. Records are never split
+    // out of their RRset, and only a cut into the answer or authority section sets TC;
+    // additional data that does not fit is silently left out.
+    // Ref: https://datatracker.ietf.org/doc/html/rfc2181#section-9
+    pub fn truncate(self, limit: usize, compress: bool) -> Self {
+        let Self {
+            header,
+            questions,
+            answers,
+            authorities,
+            additionals,
+            edns,
+            udp_payload_size,
+        } = self;
+
+        let mut enc = Encoder::new(compress);
+        enc.put(&header.as_bytes());
+        for q in questions.iter() {
+            q.encode(&mut enc);
+        }
+
+        // The OPT record goes last but always stays, so its room is set aside up front.
+        let limit = limit.saturating_sub(edns.as_ref().map_or(0, |edns| {
+            let mut enc = Encoder::new(false);
+            edns.encode(&mut enc, header.rcode());
+            enc.position()
+        }));
+
+        let (answers, complete) = fill(&mut enc, answers, limit);
+        let (authorities, complete) = match complete {
+            true => fill(&mut enc, authorities, limit),
+            false => (vec![], false),
+        };
+        let additionals = match complete {
+            true => fill(&mut enc, additionals, limit).0,
+            false => vec![],
+        };
+
+        let header = header
+            .set_tc(!complete)
+            .set_an(answers.len() as u16)
+            .set_ns(authorities.len() as u16)
+            .set_ar((additionals.len() + edns.is_some() as usize) as u16);
+
+        Self {
+            header,
+            questions,
+            answers,
+            authorities,
+            additionals,
+            edns,
+            udp_payload_size,
+        }
+    }
+
+    pub fn udp_payload_size(&self) -> usize {
+        self.udp_payload_size as usize
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
-        self.header
-            .as_bytes()
-            .into_iter()
-            .chain(self.questions.iter().flat_map(Question::as_bytes))
-            .chain(self.answers.iter().flat_map(Answer::as_bytes))
-            .collect()
+        self.encode(true)
+    }
+
+    pub fn encode(&self, compress: bool) -> Vec<u8> {
+        let mut enc = Encoder::new(compress);
+        enc.put(&self.header.as_bytes());
+
+        for q in self.questions.iter() {
+            q.encode(&mut enc);
+        }
+
+        for a in self
+            .answers
+            .iter()
+            .chain(self.authorities.iter())
+            .chain(self.additionals.iter())
+        {
+            a.encode(&mut enc);
+        }
+
+        if let Some(edns) = &self.edns {
+            edns.encode(&mut enc, self.header.rcode());
+        }
+
+        enc.into_bytes()
     }
 
     pub fn id(&self) -> u16 {
@@ -108,82 +276,91 @@ impl TryFrom<&[u8]> for Message {
             answers.push(Answer::new(&mut cursor)?);
         }
 
+        let mut authorities: Vec<Answer> = vec![];
+        for _ in 0..header.num_of_authorities() {
+            authorities.push(Answer::new(&mut cursor)?);
+        }
+
+        let mut header = header;
+        let mut additionals: Vec<Answer> = vec![];
+        let mut edns: Option<Edns> = None;
+        for _ in 0..header.num_of_additionals() {
+            let a = Answer::new(&mut cursor)?;
+
+            if a.r#type() != RecordType::Opt {
+                additionals.push(a);
+                continue;
+            }
+
+            if edns.is_some() {
+                return Err(ParseError::MultipleOpt.into());
+            }
+
+            header = header.extend_rcode((a.ttl() >> 24) as u8);
+            edns = Some(Edns::try_from(a)?);
+        }
+
         Ok(Self {
             header,
             questions,
             answers,
+            authorities,
+            additionals,
+            udp_payload_size: negotiated_payload_size(edns.as_ref()),
+            edns,
         })
     }
 }
 
-#[derive(Debug, Clone)]
-struct DomainName(String);
-
-impl DomainName {
-    fn as_bytes(&self) -> Vec<u8> {
-        self.0
-            .split('.')
-            .flat_map(label_part)
-            .chain([0u8])
-            .collect()
-    }
-
-    fn new(cursor: &mut Cursor<&[u8]>) -> Result<Self> {
-        let mut byte = utils::read_1_byte(cursor)?;
-        let mut tokens: Vec<Vec<u8>> = vec![];
-
-        while byte != 0 {
-            if byte & 0b11000000 == 0b11000000 {
-                let b0 = byte & 0b00111111;
-                let b1 = utils::read_1_byte(cursor)?;
-
-                let p: u64 = u16::from_be_bytes([b0, b1]) as u64;
-                let mut c = Cursor::new(*(cursor.get_ref()));
-                c.seek(SeekFrom::Start(p))?;
-
-                let mut pointed_tokens = Self::uncompressed_tokens(&mut c)?;
-                tokens.append(&mut pointed_tokens);
-                byte = 0;
-            } else {
-                let bytes = utils::read_n_bytes(cursor, byte as usize)?;
-                tokens.push(bytes);
-                byte = utils::read_1_byte(cursor)?;
-            }
-        }
+fn negotiated_payload_size(edns: Option<&Edns>) -> u16 {
+    edns.map_or(MIN_UDP_PAYLOAD_SIZE, |edns| {
+        edns.udp_size()
+            .clamp(MIN_UDP_PAYLOAD_SIZE, MAX_UDP_PAYLOAD_SIZE)
+    })
+}
 
-        let val = tokens
-            .into_iter()
-            .filter_map(|bytes| String::from_utf8(bytes).ok())
-            .collect::<Vec<String>>()
-            .join(".");
+// Encodes RRsets one after another until the next one would go past `limit`. Returns the
+// records that fit and whether all of them did.
+fn fill(enc: &mut Encoder, records: Vec<Answer>, limit: usize) -> (Vec<Answer>, bool) {
+    let mut kept: Vec<Answer> = vec![];
 
-        Ok(Self(val))
-    }
+    for rrset in rrsets(records) {
+        let pos = enc.position();
+        for record in rrset.iter() {
+            record.encode(enc);
+        }
 
-    fn uncompressed_tokens<R: Read>(r: &mut R) -> Result<Vec<Vec<u8>>> {
-        let mut len: usize = utils::read_1_byte(r)? as usize;
-        let mut tokens: Vec<Vec<u8>> = vec![];
+        if enc.position() > limit {
+            enc.rewind(pos);
+            return (kept, false);
+        }
+        kept.extend(rrset);
+    }
 
-        while len > 0 {
-            let bytes = utils::read_n_bytes(r, len)?;
-            tokens.push(bytes);
+    (kept, true)
+}
 
-            len = utils::read_1_byte(r)? as usize;
+// Groups consecutive records sharing owner, type and class.
+fn rrsets(records: Vec<Answer>) -> Vec<Vec<Answer>> {
+    let mut rrsets: Vec<Vec<Answer>> = vec![];
+
+    for record in records {
+        match rrsets.last_mut() {
+            Some(rrset)
+                if rrset[0].name() == record.name()
+                    && rrset[0].r#type() == record.r#type()
+                    && rrset[0].class() == record.class() =>
+            {
+                rrset.push(record)
+            }
+            _ => rrsets.push(vec![record]),
         }
-
-        Ok(tokens)
     }
-}
 
-fn label_part(substr: &str) -> Vec<u8> {
-    let length = substr.len() as u8;
-    [length]
-        .into_iter()
-        .chain(substr.as_bytes().to_vec())
-        .collect()
+    rrsets
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum RecordType {
     A,
     Ns,
@@ -201,6 +378,15 @@ pub enum RecordType {
     Minfo,
     Mx,
     Txt,
+    Aaaa,
+    Srv,
+    Naptr,
+    Sshfp,
+    Caa,
+    Opt,
+    // Only valid as QTYPE, asking for records of every type.
+    Any,
+    Unknown(u16),
 }
 
 impl RecordType {
@@ -222,6 +408,14 @@ impl RecordType {
             Self::Minfo => 14,
             Self::Mx => 15,
             Self::Txt => 16,
+            Self::Aaaa => 28,
+            Self::Srv => 33,
+            Self::Naptr => 35,
+            Self::Sshfp => 44,
+            Self::Caa => 257,
+            Self::Opt => 41,
+            Self::Any => 255,
+            Self::Unknown(code) => *code,
         }
     }
 
@@ -247,19 +441,318 @@ impl RecordType {
             14 => Self::Minfo,
             15 => Self::Mx,
             16 => Self::Txt,
-            _ => panic!("Unexpected bytes: {bytes:?}"),
+            28 => Self::Aaaa,
+            33 => Self::Srv,
+            35 => Self::Naptr,
+            44 => Self::Sshfp,
+            257 => Self::Caa,
+            41 => Self::Opt,
+            255 => Self::Any,
+            code => Self::Unknown(code),
+        }
+    }
+}
+
+impl fmt::Display for RecordType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mnemonic = match self {
+            Self::A => "A",
+            Self::Ns => "NS",
+            Self::Md => "MD",
+            Self::Mf => "MF",
+            Self::Cname => "CNAME",
+            Self::Soa => "SOA",
+            Self::Mb => "MB",
+            Self::Mg => "MG",
+            Self::Mr => "MR",
+            Self::Null => "NULL",
+            Self::Wks => "WKS",
+            Self::Ptr => "PTR",
+            Self::Hinfo => "HINFO",
+            Self::Minfo => "MINFO",
+            Self::Mx => "MX",
+            Self::Txt => "TXT",
+            Self::Aaaa => "AAAA",
+            Self::Srv => "SRV",
+            Self::Naptr => "NAPTR",
+            Self::Sshfp => "SSHFP",
+            Self::Caa => "CAA",
+            Self::Opt => "OPT",
+            Self::Any => "ANY",
+            // Ref: https://datatracker.ietf.org/doc/html/rfc3597#section-5
+            Self::Unknown(code) => return write!(f, "TYPE{code}"),
+        };
+        f.write_str(mnemonic)
+    }
+}
+
+impl FromStr for RecordType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let r#type = match s.to_ascii_uppercase().as_str() {
+            "A" => Self::A,
+            "NS" => Self::Ns,
+            "MD" => Self::Md,
+            "MF" => Self::Mf,
+            "CNAME" => Self::Cname,
+            "SOA" => Self::Soa,
+            "MB" => Self::Mb,
+            "MG" => Self::Mg,
+            "MR" => Self::Mr,
+            "NULL" => Self::Null,
+            "WKS" => Self::Wks,
+            "PTR" => Self::Ptr,
+            "HINFO" => Self::Hinfo,
+            "MINFO" => Self::Minfo,
+            "MX" => Self::Mx,
+            "TXT" => Self::Txt,
+            "AAAA" => Self::Aaaa,
+            "SRV" => Self::Srv,
+            "NAPTR" => Self::Naptr,
+            "SSHFP" => Self::Sshfp,
+            "CAA" => Self::Caa,
+            "OPT" => Self::Opt,
+            "ANY" => Self::Any,
+            other => other
+                .strip_prefix("TYPE")
+                .and_then(|code| code.parse::<u16>().ok())
+                .map(|code| Self::from_bytes(code.to_be_bytes()))
+                .ok_or(err!("Unknown record type {s}"))?,
+        };
+        Ok(r#type)
+    }
+}
+
+// Ref: https://datatracker.ietf.org/doc/html/rfc1035#section-3.2.4
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RecordClass {
+    In,
+    Ch,
+    Hs,
+    None,
+    Any,
+    Unknown(u16),
+}
+
+impl RecordClass {
+    fn as_u16(&self) -> u16 {
+        match self {
+            Self::In => 1,
+            Self::Ch => 3,
+            Self::Hs => 4,
+            Self::None => 254,
+            Self::Any => 255,
+            Self::Unknown(code) => *code,
+        }
+    }
+
+    fn as_bytes(&self) -> [u8; 2] {
+        self.as_u16().to_be_bytes()
+    }
+
+    fn from_bytes(bytes: [u8; 2]) -> Self {
+        match u16::from_be_bytes(bytes) {
+            1 => Self::In,
+            3 => Self::Ch,
+            4 => Self::Hs,
+            254 => Self::None,
+            255 => Self::Any,
+            code => Self::Unknown(code),
         }
     }
 }
 
+impl fmt::Display for RecordClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mnemonic = match self {
+            Self::In => "IN",
+            Self::Ch => "CH",
+            Self::Hs => "HS",
+            Self::None => "NONE",
+            Self::Any => "ANY",
+            // Ref: https://datatracker.ietf.org/doc/html/rfc3597#section-5
+            Self::Unknown(code) => return write!(f, "CLASS{code}"),
+        };
+        f.write_str(mnemonic)
+    }
+}
+
+impl FromStr for RecordClass {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let class = match s.to_ascii_uppercase().as_str() {
+            "IN" => Self::In,
+            "CH" => Self::Ch,
+            "HS" => Self::Hs,
+            "NONE" => Self::None,
+            "ANY" => Self::Any,
+            other => other
+                .strip_prefix("CLASS")
+                .and_then(|code| code.parse::<u16>().ok())
+                .map(|code| Self::from_bytes(code.to_be_bytes()))
+                .ok_or(err!("Unknown record class {s}"))?,
+        };
+        Ok(class)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn it_serializes_domain_name() {
-        let name = DomainName("google.com".into());
-        let expected = b"\x06google\x03com\x00".to_vec();
-        assert_eq!(name.as_bytes(), expected);
+    fn it_round_trips_modern_record_types() {
+        let bytes: Vec<u8> = [
+            // header: response, 1 question, 5 answers
+            &b"\x12\x34\x81\x80\x00\x01\x00\x05\x00\x00\x00\x00"[..],
+            b"\x01a\x00\x00\x1c\x00\x01",
+            // AAAA ::1
+            b"\x01a\x00\x00\x1c\x00\x01\x00\x00\x00\x3c\x00\x10",
+            b"\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01",
+            // SRV 10 20 5060 sip.a
+            b"\x01a\x00\x00\x21\x00\x01\x00\x00\x00\x3c\x00\x0d",
+            b"\x00\x0a\x00\x14\x13\xc4\x03sip\x01a\x00",
+            // NAPTR 100 10 "S" "SIP+D2U" "" _sip._udp.a
+            b"\x01a\x00\x00\x23\x00\x01\x00\x00\x00\x3c\x00\x1c",
+            b"\x00\x64\x00\x0a\x01S\x07SIP+D2U\x00\x04_sip\x04_udp\x01a\x00",
+            // SSHFP 1 1 <fingerprint>
+            b"\x01a\x00\x00\x2c\x00\x01\x00\x00\x00\x3c\x00\x06",
+            b"\x01\x01\xde\xad\xbe\xef",
+            // CAA 0 issue "ca.example"
+            b"\x01a\x00\x01\x01\x00\x01\x00\x00\x00\x3c\x00\x11",
+            b"\x00\x05issueca.example",
+        ]
+        .concat();
+
+        let msg = Message::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(msg.answers.len(), 5);
+        assert_eq!(msg.encode(false), bytes);
+    }
+
+    #[test]
+    fn it_parses_authority_and_additional_sections() {
+        let bytes: Vec<u8> = [
+            // header: response, 1 question, 0 answers, 1 authority, 1 additional
+            &b"\x00\x01\x81\x00\x00\x01\x00\x00\x00\x01\x00\x01"[..],
+            b"\x03www\x01a\x00\x00\x01\x00\x01",
+            // a NS ns.a
+            b"\x01a\x00\x00\x02\x00\x01\x00\x00\x0e\x10\x00\x06\x02ns\x01a\x00",
+            // ns.a A 10.0.0.1
+            b"\x02ns\x01a\x00\x00\x01\x00\x01\x00\x00\x0e\x10\x00\x04\x0a\x00\x00\x01",
+        ]
+        .concat();
+
+        let msg = Message::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(msg.authorities.len(), 1);
+        assert_eq!(msg.additionals.len(), 1);
+        assert_eq!(msg.encode(false), bytes);
+
+        let reply = Message::reply(msg)
+            .set_authority(Answer::new(&mut Cursor::new(&bytes[23..42])).unwrap());
+        assert_eq!(reply.header.num_of_authorities(), 1);
+        assert_eq!(reply.header.num_of_additionals(), 0);
+    }
+
+    #[test]
+    fn it_parses_edns_opt_record() {
+        let bytes: Vec<u8> = [
+            &b"\x00\x01\x01\x00\x00\x01\x00\x00\x00\x00\x00\x01"[..],
+            b"\x01a\x00\x00\x01\x00\x01",
+            // OPT: payload 4096, DO bit, COOKIE option
+            b"\x00\x00\x29\x10\x00\x00\x00\x80\x00\x00\x0c",
+            b"\x00\x0a\x00\x08\x01\x02\x03\x04\x05\x06\x07\x08",
+        ]
+        .concat();
+
+        let msg = Message::try_from(bytes.as_slice()).unwrap();
+        let edns = msg.edns.as_ref().unwrap();
+        assert!(msg.additionals.is_empty());
+        assert_eq!(edns.udp_size(), 4096);
+        assert_eq!(edns.version(), 0);
+        assert!(edns.dnssec_ok());
+        assert_eq!(edns.options()[0].code, 10);
+        assert_eq!(msg.udp_payload_size(), 4096);
+        assert_eq!(msg.encode(false), bytes);
+
+        // extended RCODE 16 (BADVERS) split between the header and the OPT TTL
+        let bytes = b"\x00\x01\x81\x00\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x29\x02\x00\x01\x00\x00\x00\x00\x00";
+        let msg = Message::try_from(&bytes[..]).unwrap();
+        assert_eq!(msg.header.rcode(), Rcode::BadVers);
+        assert_eq!(msg.udp_payload_size(), 512);
+        assert_eq!(msg.encode(false), bytes);
+
+        let bytes = b"\x00\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x02\x00\x00\x29\x02\x00\x00\x00\x00\x00\x00\x00\x00\x00\x29\x02\x00\x00\x00\x00\x00\x00\x00";
+        let err = Message::try_from(&bytes[..]).unwrap_err();
+        assert!(matches!(err, Error::Parse(ParseError::MultipleOpt)));
+    }
+
+    #[test]
+    fn it_compresses_repeated_names() {
+        let bytes: Vec<u8> = [
+            &b"\x00\x01\x81\x00\x00\x01\x00\x02\x00\x00\x00\x00"[..],
+            b"\x03www\x07example\x03com\x00\x00\x0f\x00\x01",
+            b"\x03WWW\x07example\x03com\x00\x00\x0f\x00\x01\x00\x00\x00\x3c\x00\x12",
+            b"\x00\x0a\x02mx\x07example\x03com\x00",
+            b"\x03www\x07example\x03com\x00\x00\x21\x00\x01\x00\x00\x00\x3c\x00\x16",
+            b"\x00\x00\x00\x00\x00\x19\x02mx\x07example\x03com\x00",
+        ]
+        .concat();
+
+        let msg = Message::try_from(bytes.as_slice()).unwrap();
+        let compressed = msg.as_bytes();
+
+        let expected: Vec<u8> = [
+            &b"\x00\x01\x81\x00\x00\x01\x00\x02\x00\x00\x00\x00"[..],
+            b"\x03www\x07example\x03com\x00\x00\x0f\x00\x01",
+            // owner matches case-insensitively, the MX exchange reuses "example.com"
+            b"\xc0\x0c\x00\x0f\x00\x01\x00\x00\x00\x3c\x00\x07\x00\x0a\x02mx\xc0\x10",
+            // SRV targets must not be compressed
+            b"\xc0\x0c\x00\x21\x00\x01\x00\x00\x00\x3c\x00\x16",
+            b"\x00\x00\x00\x00\x00\x19\x02mx\x07example\x03com\x00",
+        ]
+        .concat();
+        assert_eq!(compressed, expected);
+
+        let decompressed = Message::try_from(compressed.as_slice()).unwrap();
+        assert_eq!(
+            decompressed.encode(false),
+            msg.encode(false).to_ascii_lowercase()
+        );
+    }
+
+    #[test]
+    fn it_truncates_whole_rrsets() {
+        let query = b"\x00\x01\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x01a\x00\x00\x01\x00\x01";
+        let msg = Message::try_from(&query[..]).unwrap();
+        let q = msg.questions[0].clone();
+        let record = |name: &str, last: u8| {
+            let bytes: Vec<u8> = [
+                name.parse::<DomainName>().unwrap().as_bytes(),
+                b"\x00\x01\x00\x01\x00\x00\x00\x3c\x00\x04\x0a\x00\x00".to_vec(),
+                vec![last],
+            ]
+            .concat();
+            Answer::new(&mut Cursor::new(bytes.as_slice())).unwrap()
+        };
+
+        let mut reply = Message::reply(msg);
+        // 30 records of one RRset, then a second RRset of 10
+        for i in 0..30 {
+            reply = reply.set_answer(record("a", i));
+        }
+        for i in 0..10 {
+            reply = reply.set_answer(record("b", i));
+        }
+        reply = reply.set_additional(record("c", 0));
+
+        let bytes = reply.truncate(512, true).encode(true);
+        let truncated = Message::try_from(bytes.as_slice()).unwrap();
+        assert!(bytes.len() <= 512);
+        assert_eq!(truncated.header.num_of_an(), 30);
+        assert_eq!(truncated.questions[0].name(), q.name());
+        assert!(truncated.additionals.is_empty());
+        assert_eq!(bytes[2] & 0b00000010, 0b00000010);
     }
 }
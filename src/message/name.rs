@@ -0,0 +1,359 @@
+use crate::{Error, ParseError, Result};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io::Cursor;
+use std::str::FromStr;
+
+// Ref: https://datatracker.ietf.org/doc/html/rfc1035#section-2.3.4
+const MAX_LABEL_LENGTH: usize = 63;
+const MAX_NAME_LENGTH: usize = 255;
+const MAX_POINTER_HOPS: usize = 128;
+
+// Labels are kept as raw bytes since DNS allows any octet in them. Comparison ignores
+// ASCII case as required by RFC 4343.
+#[derive(Debug, Clone)]
+pub struct DomainName(Vec<Vec<u8>>);
+
+impl DomainName {
+    pub fn root() -> Self {
+        Self(vec![])
+    }
+
+    pub fn from_labels(labels: Vec<Vec<u8>>) -> Result<Self> {
+        let mut length = 1;
+
+        for label in labels.iter() {
+            if label.is_empty() {
+                return Err(ParseError::EmptyLabel.into());
+            }
+            if label.len() > MAX_LABEL_LENGTH {
+                return Err(ParseError::LabelTooLong(label.len()).into());
+            }
+            length += label.len() + 1;
+        }
+
+        if length > MAX_NAME_LENGTH {
+            return Err(ParseError::NameTooLong.into());
+        }
+
+        Ok(Self(labels))
+    }
+
+    pub fn labels(&self) -> &[Vec<u8>] {
+        &self.0
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // The name one label up, or nothing for the root.
+    pub fn parent(&self) -> Option<Self> {
+        self.0.split_first().map(|(_, rest)| Self(rest.to_vec()))
+    }
+
+    // The wildcard owner `*` directly below the name.
+    // Ref: https://datatracker.ietf.org/doc/html/rfc4592#section-2.1.1
+    pub fn wildcard(&self) -> Result<Self> {
+        Self::from_labels([vec![b"*".to_vec()], self.0.clone()].concat())
+    }
+
+    // True for the name itself as well as any name below it.
+    pub fn is_subdomain_of(&self, other: &Self) -> bool {
+        self.0.len() >= other.0.len()
+            && self.0[self.0.len() - other.0.len()..]
+                .iter()
+                .zip(other.0.iter())
+                .all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        self.0
+            .iter()
+            .flat_map(|label| label_part(label))
+            .chain([0u8])
+            .collect()
+    }
+
+    // Every pointer must jump before the label sequence it interrupts, so a chain of
+    // pointers strictly moves towards the start of the message and cannot loop.
+    pub fn new(cursor: &mut Cursor<&[u8]>) -> Result<Self> {
+        let bytes: &[u8] = cursor.get_ref();
+        let mut pos = cursor.position() as usize;
+        let mut limit = pos;
+        let mut resume_at: Option<usize> = None;
+        let mut hops = 0;
+        let mut length = 1;
+        let mut labels: Vec<Vec<u8>> = vec![];
+
+        loop {
+            let byte = *bytes.get(pos).ok_or(ParseError::UnexpectedEnd)?;
+
+            match byte & 0b11000000 {
+                0b11000000 => {
+                    let b1 = *bytes.get(pos + 1).ok_or(ParseError::UnexpectedEnd)?;
+                    let to = u16::from_be_bytes([byte & 0b00111111, b1]) as usize;
+
+                    if to >= limit {
+                        return Err(ParseError::ForwardPointer { at: pos, to }.into());
+                    }
+
+                    hops += 1;
+                    if hops > MAX_POINTER_HOPS {
+                        return Err(ParseError::TooManyPointers(MAX_POINTER_HOPS).into());
+                    }
+
+                    resume_at.get_or_insert(pos + 2);
+                    limit = to;
+                    pos = to;
+                }
+                0b00000000 if byte == 0 => {
+                    pos += 1;
+                    break;
+                }
+                0b00000000 => {
+                    let len = byte as usize;
+
+                    length += len + 1;
+                    if length > MAX_NAME_LENGTH {
+                        return Err(ParseError::NameTooLong.into());
+                    }
+
+                    let label = bytes
+                        .get(pos + 1..pos + 1 + len)
+                        .ok_or(ParseError::UnexpectedEnd)?;
+                    labels.push(label.to_vec());
+                    pos += 1 + len;
+                }
+                _ => return Err(ParseError::ReservedLabelType(byte).into()),
+            }
+        }
+
+        cursor.set_position(resume_at.unwrap_or(pos) as u64);
+
+        Ok(Self(labels))
+    }
+}
+
+impl PartialEq for DomainName {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len()
+            && self
+                .0
+                .iter()
+                .zip(other.0.iter())
+                .all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+}
+
+impl Eq for DomainName {}
+
+impl Hash for DomainName {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for label in self.0.iter() {
+            label.to_ascii_lowercase().hash(state);
+        }
+    }
+}
+
+// Presentation format with the escapes of RFC 1035 section 5.1.
+impl fmt::Display for DomainName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_root() {
+            return f.write_str(".");
+        }
+
+        for label in self.0.iter() {
+            for &b in label {
+                match b {
+                    b'.' | b'\\' | b'"' | b'(' | b')' | b';' | b'@' | b'$' => {
+                        write!(f, "\\{}", b as char)?
+                    }
+                    0x21..=0x7e => write!(f, "{}", b as char)?,
+                    _ => write!(f, "\\{b:03}")?,
+                }
+            }
+            f.write_str(".")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for DomainName {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s == "." {
+            return Ok(Self::root());
+        }
+
+        let mut labels: Vec<Vec<u8>> = vec![];
+        let mut label: Vec<u8> = vec![];
+        let mut bytes = s.bytes();
+
+        while let Some(b) = bytes.next() {
+            match b {
+                b'\\' => {
+                    let c = bytes.next().ok_or(ParseError::InvalidEscape)?;
+
+                    if c.is_ascii_digit() {
+                        let digits = [c, next_digit(&mut bytes)?, next_digit(&mut bytes)?];
+                        let value = digits
+                            .iter()
+                            .fold(0u16, |acc, d| acc * 10 + (d - b'0') as u16);
+                        label.push(u8::try_from(value).map_err(|_| ParseError::InvalidEscape)?);
+                    } else {
+                        label.push(c);
+                    }
+                }
+                b'.' => {
+                    if label.is_empty() {
+                        return Err(ParseError::EmptyLabel.into());
+                    }
+                    labels.push(std::mem::take(&mut label));
+                }
+                _ => label.push(b),
+            }
+        }
+
+        if !label.is_empty() {
+            labels.push(label);
+        }
+
+        Self::from_labels(labels)
+    }
+}
+
+fn next_digit<I: Iterator<Item = u8>>(bytes: &mut I) -> Result<u8> {
+    bytes
+        .next()
+        .filter(u8::is_ascii_digit)
+        .ok_or(ParseError::InvalidEscape.into())
+}
+
+pub fn label_part(label: &[u8]) -> Vec<u8> {
+    let length = label.len() as u8;
+    [length].into_iter().chain(label.iter().copied()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_serializes_domain_name() {
+        let name: DomainName = "google.com".parse().unwrap();
+        let expected = b"\x06google\x03com\x00".to_vec();
+        assert_eq!(name.as_bytes(), expected);
+    }
+
+    fn parse_name(bytes: &[u8], at: u64) -> Result<DomainName> {
+        let mut cursor = Cursor::new(bytes);
+        cursor.set_position(at);
+        DomainName::new(&mut cursor)
+    }
+
+    #[test]
+    fn it_follows_chained_pointers() {
+        // "com" at 0, "example" + ptr(0) at 5, "www" + ptr(5) at 15
+        let bytes = b"\x03com\x00\x07example\xc0\x00\x03www\xc0\x05\xff";
+        let mut cursor = Cursor::new(&bytes[..]);
+        cursor.set_position(15);
+
+        let name = DomainName::new(&mut cursor).unwrap();
+        assert_eq!(name.to_string(), "www.example.com.");
+        assert_eq!(cursor.position(), 21);
+    }
+
+    #[test]
+    fn it_rejects_malicious_names() {
+        // pointer to itself
+        let err = parse_name(b"\x03www\xc0\x00", 0).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Parse(ParseError::ForwardPointer { .. })
+        ));
+
+        // pointer to a later offset
+        let err = parse_name(b"\xc0\x02\x00", 0).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Parse(ParseError::ForwardPointer { .. })
+        ));
+
+        // pointers bouncing between two labels
+        let err = parse_name(b"\x01a\xc0\x04\x01b\xc0\x00", 4).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Parse(ParseError::ForwardPointer { .. })
+        ));
+
+        let long: Vec<u8> = [b"\x3f".as_slice(); 5]
+            .iter()
+            .flat_map(|len| len.iter().copied().chain([b'a'; 63]))
+            .chain([0])
+            .collect();
+        let err = parse_name(&long, 0).unwrap_err();
+        assert!(matches!(err, Error::Parse(ParseError::NameTooLong)));
+
+        let err = parse_name(b"\x41abc\x00", 0).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Parse(ParseError::ReservedLabelType(0x41))
+        ));
+
+        let err = parse_name(b"\x05ab", 0).unwrap_err();
+        assert!(matches!(err, Error::Parse(ParseError::UnexpectedEnd)));
+    }
+
+    #[test]
+    fn it_keeps_binary_labels() {
+        let name = parse_name(b"\x04a.b\xff\x03c d\x00", 0).unwrap();
+        assert_eq!(name.labels(), [b"a.b\xff".to_vec(), b"c d".to_vec()]);
+        assert_eq!(name.to_string(), "a\\.b\\255.c\\032d.");
+
+        let parsed: DomainName = name.to_string().parse().unwrap();
+        assert_eq!(parsed.as_bytes(), b"\x04a.b\xff\x03c d\x00");
+    }
+
+    #[test]
+    fn it_compares_names_case_insensitively() {
+        let a: DomainName = "WWW.Example.COM.".parse().unwrap();
+        let b: DomainName = "www.example.com".parse().unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.to_string(), "WWW.Example.COM.");
+
+        let mut set = std::collections::HashSet::new();
+        set.insert(a);
+        assert!(set.contains(&b));
+    }
+
+    #[test]
+    fn it_checks_subdomains() {
+        let name: DomainName = "www.Example.com".parse().unwrap();
+        assert!(name.is_subdomain_of(&"example.COM".parse().unwrap()));
+        assert!(name.is_subdomain_of(&name));
+        assert!(name.is_subdomain_of(&DomainName::root()));
+        assert!(!name.is_subdomain_of(&"ample.com".parse().unwrap()));
+        assert!(!DomainName::root().is_subdomain_of(&name));
+    }
+
+    #[test]
+    fn it_walks_up_to_the_parent() {
+        let name: DomainName = "www.example.com".parse().unwrap();
+        let parent = name.parent().unwrap();
+        assert_eq!(parent, "example.com".parse().unwrap());
+        assert_eq!(parent.wildcard().unwrap(), "*.example.com".parse().unwrap());
+        assert!(DomainName::root().parent().is_none());
+    }
+
+    #[test]
+    fn it_rejects_invalid_presentation_names() {
+        assert!("a..b".parse::<DomainName>().is_err());
+        assert!("a\\256".parse::<DomainName>().is_err());
+        assert!("a\\12".parse::<DomainName>().is_err());
+        assert!("a".repeat(64).parse::<DomainName>().is_err());
+        assert!(".".parse::<DomainName>().unwrap().is_root());
+    }
+}
@@ -1,12 +1,12 @@
-use super::{DomainName, RecordType};
+use super::{DomainName, Encoder, RecordClass, RecordType};
 use crate::{utils, Result};
 use std::io::Cursor;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Question {
     name: DomainName,
     r#type: RecordType,
-    class: u16,
+    class: RecordClass,
 }
 
 impl Question {
@@ -14,33 +14,43 @@ impl Question {
         let name = DomainName::new(cursor)?;
         let bytes = utils::read_2_bytes(cursor)?;
         let r#type = RecordType::from_bytes(bytes);
-        let _ = utils::read_2_bytes(cursor)?;
+        let bytes = utils::read_2_bytes(cursor)?;
+        let class = RecordClass::from_bytes(bytes);
 
         Ok(Self {
             name,
             r#type,
-            class: 1,
+            class,
         })
     }
 
-    pub fn as_bytes(&self) -> Vec<u8> {
-        self.name
-            .as_bytes()
-            .into_iter()
-            .chain(self.r#type.as_bytes())
-            .chain(self.class.to_be_bytes())
-            .collect()
+    pub fn from_parts(name: DomainName, r#type: RecordType, class: RecordClass) -> Self {
+        Self {
+            name,
+            r#type,
+            class,
+        }
+    }
+
+    pub fn set_name(self, name: DomainName) -> Self {
+        Self { name, ..self }
+    }
+
+    pub fn encode(&self, enc: &mut Encoder) {
+        enc.put_name(&self.name, true);
+        enc.put(&self.r#type.as_bytes());
+        enc.put(&self.class.as_bytes());
     }
 
-    pub(super) fn name(&self) -> &DomainName {
+    pub fn name(&self) -> &DomainName {
         &self.name
     }
 
-    pub(super) fn r#type(&self) -> RecordType {
+    pub fn r#type(&self) -> RecordType {
         self.r#type
     }
 
-    pub(super) fn class(&self) -> u16 {
+    pub fn class(&self) -> RecordClass {
         self.class
     }
 }
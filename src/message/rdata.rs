@@ -0,0 +1,445 @@
+use super::{DomainName, EdnsOption, Encoder, RecordType};
+use crate::{utils, Result};
+use std::fmt;
+use std::io::Cursor;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+// Ref: https://datatracker.ietf.org/doc/html/rfc1035#section-3.3
+//      https://datatracker.ietf.org/doc/html/rfc3596 (AAAA)
+//      https://datatracker.ietf.org/doc/html/rfc2782 (SRV)
+//      https://datatracker.ietf.org/doc/html/rfc3403 (NAPTR)
+//      https://datatracker.ietf.org/doc/html/rfc4255 (SSHFP)
+//      https://datatracker.ietf.org/doc/html/rfc8659 (CAA)
+//      https://datatracker.ietf.org/doc/html/rfc6891 (OPT)
+//      https://datatracker.ietf.org/doc/html/rfc3597 (Unknown)
+#[derive(Debug, Clone)]
+pub enum Rdata {
+    A([u8; 4]),
+    Ns(DomainName),
+    Md(DomainName),
+    Mf(DomainName),
+    Cname(DomainName),
+    Soa {
+        mname: DomainName,
+        rname: DomainName,
+        serial: u32,
+        refresh: u32,
+        retry: u32,
+        expire: u32,
+        minimum: u32,
+    },
+    Mb(DomainName),
+    Mg(DomainName),
+    Mr(DomainName),
+    Null(Vec<u8>),
+    Wks {
+        address: [u8; 4],
+        protocol: u8,
+        bitmap: Vec<u8>,
+    },
+    Ptr(DomainName),
+    Hinfo {
+        cpu: Vec<u8>,
+        os: Vec<u8>,
+    },
+    Minfo {
+        rmailbx: DomainName,
+        emailbx: DomainName,
+    },
+    Mx {
+        preference: u16,
+        exchange: DomainName,
+    },
+    Txt(Vec<Vec<u8>>),
+    Aaaa([u8; 16]),
+    Srv {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: DomainName,
+    },
+    Naptr {
+        order: u16,
+        preference: u16,
+        flags: Vec<u8>,
+        services: Vec<u8>,
+        regexp: Vec<u8>,
+        replacement: DomainName,
+    },
+    Sshfp {
+        algorithm: u8,
+        fp_type: u8,
+        fingerprint: Vec<u8>,
+    },
+    Caa {
+        flags: u8,
+        tag: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Opt(Vec<EdnsOption>),
+    // RDATA of a type this server does not understand, kept opaque so it can be relayed verbatim.
+    Unknown(Vec<u8>),
+}
+
+impl Rdata {
+    pub fn new(cursor: &mut Cursor<&[u8]>, r#type: RecordType, length: u16) -> Result<Self> {
+        let end = cursor.position() + length as u64;
+
+        if end > cursor.get_ref().len() as u64 {
+            return Err(err!("RDATA length {length} exceeds the message"));
+        }
+
+        let data = match r#type {
+            RecordType::A => Self::A(utils::read_4_bytes(cursor)?),
+            RecordType::Ns => Self::Ns(DomainName::new(cursor)?),
+            RecordType::Md => Self::Md(DomainName::new(cursor)?),
+            RecordType::Mf => Self::Mf(DomainName::new(cursor)?),
+            RecordType::Cname => Self::Cname(DomainName::new(cursor)?),
+            RecordType::Soa => Self::Soa {
+                mname: DomainName::new(cursor)?,
+                rname: DomainName::new(cursor)?,
+                serial: read_u32(cursor)?,
+                refresh: read_u32(cursor)?,
+                retry: read_u32(cursor)?,
+                expire: read_u32(cursor)?,
+                minimum: read_u32(cursor)?,
+            },
+            RecordType::Mb => Self::Mb(DomainName::new(cursor)?),
+            RecordType::Mg => Self::Mg(DomainName::new(cursor)?),
+            RecordType::Mr => Self::Mr(DomainName::new(cursor)?),
+            RecordType::Null => Self::Null(utils::read_n_bytes(cursor, length as usize)?),
+            RecordType::Wks => {
+                let address = utils::read_4_bytes(cursor)?;
+                let protocol = utils::read_1_byte(cursor)?;
+                let bitmap = read_until(cursor, end)?;
+                Self::Wks {
+                    address,
+                    protocol,
+                    bitmap,
+                }
+            }
+            RecordType::Ptr => Self::Ptr(DomainName::new(cursor)?),
+            RecordType::Hinfo => Self::Hinfo {
+                cpu: read_character_string(cursor)?,
+                os: read_character_string(cursor)?,
+            },
+            RecordType::Minfo => Self::Minfo {
+                rmailbx: DomainName::new(cursor)?,
+                emailbx: DomainName::new(cursor)?,
+            },
+            RecordType::Mx => Self::Mx {
+                preference: read_u16(cursor)?,
+                exchange: DomainName::new(cursor)?,
+            },
+            RecordType::Txt => {
+                let mut strings: Vec<Vec<u8>> = vec![];
+                while cursor.position() < end {
+                    strings.push(read_character_string(cursor)?);
+                }
+                Self::Txt(strings)
+            }
+            RecordType::Aaaa => Self::Aaaa(utils::read_16_bytes(cursor)?),
+            RecordType::Srv => Self::Srv {
+                priority: read_u16(cursor)?,
+                weight: read_u16(cursor)?,
+                port: read_u16(cursor)?,
+                target: DomainName::new(cursor)?,
+            },
+            RecordType::Naptr => Self::Naptr {
+                order: read_u16(cursor)?,
+                preference: read_u16(cursor)?,
+                flags: read_character_string(cursor)?,
+                services: read_character_string(cursor)?,
+                regexp: read_character_string(cursor)?,
+                replacement: DomainName::new(cursor)?,
+            },
+            RecordType::Sshfp => Self::Sshfp {
+                algorithm: utils::read_1_byte(cursor)?,
+                fp_type: utils::read_1_byte(cursor)?,
+                fingerprint: read_until(cursor, end)?,
+            },
+            RecordType::Caa => Self::Caa {
+                flags: utils::read_1_byte(cursor)?,
+                tag: read_character_string(cursor)?,
+                value: read_until(cursor, end)?,
+            },
+            RecordType::Opt => {
+                let mut options: Vec<EdnsOption> = vec![];
+                while cursor.position() < end {
+                    let code = read_u16(cursor)?;
+                    let len = read_u16(cursor)?;
+                    let data = utils::read_n_bytes(cursor, len as usize)?;
+                    options.push(EdnsOption { code, data });
+                }
+                Self::Opt(options)
+            }
+            RecordType::Any | RecordType::Unknown(_) => {
+                Self::Unknown(utils::read_n_bytes(cursor, length as usize)?)
+            }
+        };
+
+        if cursor.position() != end {
+            return Err(err!(
+                "RDATA of {type:?} record does not match its length {length}",
+                type = r#type
+            ));
+        }
+
+        Ok(data)
+    }
+
+    // Only the record types defined in RFC 1035 may have their embedded names compressed.
+    // Ref: https://datatracker.ietf.org/doc/html/rfc3597#section-4
+    pub fn encode(&self, enc: &mut Encoder) {
+        match self {
+            Self::A(bytes) => enc.put(bytes),
+            Self::Ns(name)
+            | Self::Md(name)
+            | Self::Mf(name)
+            | Self::Cname(name)
+            | Self::Mb(name)
+            | Self::Mg(name)
+            | Self::Mr(name)
+            | Self::Ptr(name) => enc.put_name(name, true),
+            Self::Soa {
+                mname,
+                rname,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+            } => {
+                enc.put_name(mname, true);
+                enc.put_name(rname, true);
+                enc.put(&serial.to_be_bytes());
+                enc.put(&refresh.to_be_bytes());
+                enc.put(&retry.to_be_bytes());
+                enc.put(&expire.to_be_bytes());
+                enc.put(&minimum.to_be_bytes());
+            }
+            Self::Null(bytes) => enc.put(bytes),
+            Self::Wks {
+                address,
+                protocol,
+                bitmap,
+            } => {
+                enc.put(address);
+                enc.put(&[*protocol]);
+                enc.put(bitmap);
+            }
+            Self::Hinfo { cpu, os } => {
+                enc.put(&character_string(cpu));
+                enc.put(&character_string(os));
+            }
+            Self::Minfo { rmailbx, emailbx } => {
+                enc.put_name(rmailbx, true);
+                enc.put_name(emailbx, true);
+            }
+            Self::Mx {
+                preference,
+                exchange,
+            } => {
+                enc.put(&preference.to_be_bytes());
+                enc.put_name(exchange, true);
+            }
+            Self::Txt(strings) => {
+                for s in strings {
+                    enc.put(&character_string(s));
+                }
+            }
+            Self::Aaaa(bytes) => enc.put(bytes),
+            Self::Srv {
+                priority,
+                weight,
+                port,
+                target,
+            } => {
+                enc.put(&priority.to_be_bytes());
+                enc.put(&weight.to_be_bytes());
+                enc.put(&port.to_be_bytes());
+                enc.put_name(target, false);
+            }
+            Self::Naptr {
+                order,
+                preference,
+                flags,
+                services,
+                regexp,
+                replacement,
+            } => {
+                enc.put(&order.to_be_bytes());
+                enc.put(&preference.to_be_bytes());
+                enc.put(&character_string(flags));
+                enc.put(&character_string(services));
+                enc.put(&character_string(regexp));
+                enc.put_name(replacement, false);
+            }
+            Self::Sshfp {
+                algorithm,
+                fp_type,
+                fingerprint,
+            } => {
+                enc.put(&[*algorithm, *fp_type]);
+                enc.put(fingerprint);
+            }
+            Self::Caa { flags, tag, value } => {
+                enc.put(&[*flags]);
+                enc.put(&character_string(tag));
+                enc.put(value);
+            }
+            Self::Opt(options) => Self::encode_options(options, enc),
+            Self::Unknown(bytes) => enc.put(bytes),
+        }
+    }
+
+    pub fn encode_options(options: &[EdnsOption], enc: &mut Encoder) {
+        for option in options {
+            enc.put(&option.code.to_be_bytes());
+            enc.put(&(option.data.len() as u16).to_be_bytes());
+            enc.put(&option.data);
+        }
+    }
+}
+
+// Presentation format of RDATA as used in master files.
+impl fmt::Display for Rdata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::A(bytes) => write!(f, "{}", Ipv4Addr::from(*bytes)),
+            Self::Ns(name)
+            | Self::Md(name)
+            | Self::Mf(name)
+            | Self::Cname(name)
+            | Self::Mb(name)
+            | Self::Mg(name)
+            | Self::Mr(name)
+            | Self::Ptr(name) => write!(f, "{name}"),
+            Self::Soa {
+                mname,
+                rname,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+            } => write!(
+                f,
+                "{mname} {rname} {serial} {refresh} {retry} {expire} {minimum}"
+            ),
+            Self::Wks {
+                address,
+                protocol,
+                bitmap,
+            } => {
+                write!(f, "{} {protocol}", Ipv4Addr::from(*address))?;
+                for (i, byte) in bitmap.iter().enumerate() {
+                    for bit in 0..8 {
+                        if byte & (0b10000000 >> bit) != 0 {
+                            write!(f, " {}", i * 8 + bit)?;
+                        }
+                    }
+                }
+                Ok(())
+            }
+            Self::Hinfo { cpu, os } => write!(f, "{} {}", quoted(cpu), quoted(os)),
+            Self::Minfo { rmailbx, emailbx } => write!(f, "{rmailbx} {emailbx}"),
+            Self::Mx {
+                preference,
+                exchange,
+            } => write!(f, "{preference} {exchange}"),
+            Self::Txt(strings) => {
+                let strings: Vec<String> = strings.iter().map(|s| quoted(s)).collect();
+                write!(f, "{}", strings.join(" "))
+            }
+            Self::Aaaa(bytes) => write!(f, "{}", Ipv6Addr::from(*bytes)),
+            Self::Srv {
+                priority,
+                weight,
+                port,
+                target,
+            } => write!(f, "{priority} {weight} {port} {target}"),
+            Self::Naptr {
+                order,
+                preference,
+                flags,
+                services,
+                regexp,
+                replacement,
+            } => write!(
+                f,
+                "{order} {preference} {} {} {} {replacement}",
+                quoted(flags),
+                quoted(services),
+                quoted(regexp)
+            ),
+            Self::Sshfp {
+                algorithm,
+                fp_type,
+                fingerprint,
+            } => write!(f, "{algorithm} {fp_type} {}", hex(fingerprint)),
+            Self::Caa { flags, tag, value } => write!(
+                f,
+                "{flags} {} {}",
+                String::from_utf8_lossy(tag),
+                quoted(value)
+            ),
+            Self::Opt(options) => {
+                let mut enc = Encoder::new(false);
+                Self::encode_options(options, &mut enc);
+                write!(f, "{}", Self::Unknown(enc.into_bytes()))
+            }
+            // Ref: https://datatracker.ietf.org/doc/html/rfc3597#section-5
+            Self::Null(bytes) | Self::Unknown(bytes) if bytes.is_empty() => write!(f, "\\# 0"),
+            Self::Null(bytes) | Self::Unknown(bytes) => {
+                write!(f, "\\# {} {}", bytes.len(), hex(bytes))
+            }
+        }
+    }
+}
+
+fn read_u16(cursor: &mut Cursor<&[u8]>) -> Result<u16> {
+    utils::read_2_bytes(cursor).map(u16::from_be_bytes)
+}
+
+fn read_u32(cursor: &mut Cursor<&[u8]>) -> Result<u32> {
+    utils::read_4_bytes(cursor).map(u32::from_be_bytes)
+}
+
+fn read_until(cursor: &mut Cursor<&[u8]>, end: u64) -> Result<Vec<u8>> {
+    let n = end.saturating_sub(cursor.position()) as usize;
+    utils::read_n_bytes(cursor, n)
+}
+
+// <character-string> is a single length octet followed by that number of characters.
+fn read_character_string(cursor: &mut Cursor<&[u8]>) -> Result<Vec<u8>> {
+    let len = utils::read_1_byte(cursor)?;
+    utils::read_n_bytes(cursor, len as usize)
+}
+
+fn character_string(bytes: &[u8]) -> Vec<u8> {
+    [bytes.len() as u8]
+        .into_iter()
+        .chain(bytes.iter().copied())
+        .collect()
+}
+
+fn quoted(bytes: &[u8]) -> String {
+    let mut s = String::from('"');
+    for &b in bytes {
+        match b {
+            b'"' | b'\\' => {
+                s.push('\\');
+                s.push(b as char);
+            }
+            0x20..=0x7e => s.push(b as char),
+            _ => s.push_str(&format!("\\{b:03}")),
+        }
+    }
+    s.push('"');
+    s
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
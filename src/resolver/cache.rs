@@ -0,0 +1,728 @@
+use super::{Answer, DomainName, Question, Rcode, Rdata, RecordClass, RecordType, MAX_CNAMES};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const DEFAULT_CAPACITY: usize = 10_000;
+const DEFAULT_NEGATIVE_CAPACITY: usize = 2_000;
+const DEFAULT_MIN_TTL: u32 = 0;
+const DEFAULT_MAX_TTL: u32 = 86_400;
+// Ref: https://datatracker.ietf.org/doc/html/rfc2308#section-5
+const MAX_NEGATIVE_TTL: u32 = 10_800;
+// Expired entries are still served for this long while the upstream cannot refresh them,
+// with `STALE_TTL` in place of their own TTL.
+// Ref: https://datatracker.ietf.org/doc/html/rfc8767#section-5
+const DEFAULT_MAX_STALE: Duration = Duration::from_secs(86_400);
+const STALE_TTL: u32 = 30;
+// An entry asked for this many times is refreshed once less than a tenth of its TTL is
+// left, so that popular names never drop out of the cache.
+const PREFETCH_MIN_HITS: u32 = 3;
+const PREFETCH_TTL_DIVISOR: u32 = 10;
+
+type Key = (DomainName, RecordType, RecordClass);
+// A missing type marks a name that does not exist at all, whatever the type asked for.
+type NegativeKey = (DomainName, Option<RecordType>, RecordClass);
+
+// RRsets from upstream answers, along with NXDOMAIN and NODATA results. Both evict their
+// least recently used entry once they hold their own `capacity` of entries.
+#[derive(Debug)]
+pub struct Cache {
+    min_ttl: u32,
+    max_ttl: u32,
+    max_stale: Duration,
+    positive: Mutex<Lru<Key, Vec<Answer>>>,
+    negative: Mutex<Lru<NegativeKey, Negative>>,
+    hits: AtomicU64,
+    stale_hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+// What the cache knows about a question, in the shape of a reply. `refresh` asks for the
+// question to be resolved again because part of the answer is stale or about to expire.
+#[derive(Debug)]
+pub struct Cached {
+    pub rcode: Rcode,
+    pub answers: Vec<Answer>,
+    pub authorities: Vec<Answer>,
+    pub stale: bool,
+    pub refresh: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Negative {
+    rcode: Rcode,
+    soa: Answer,
+}
+
+#[derive(Debug)]
+struct Lru<K, V> {
+    capacity: usize,
+    entries: HashMap<K, Entry<V>>,
+    // Last use of each entry, oldest first.
+    recency: BTreeMap<u64, K>,
+    clock: u64,
+}
+
+#[derive(Debug)]
+struct Entry<V> {
+    value: V,
+    ttl: u32,
+    expires_at: Instant,
+    hits: u32,
+    used_at: u64,
+}
+
+#[derive(Debug)]
+struct Hit<V> {
+    value: V,
+    ttl: u32,
+    stale: bool,
+    prefetch: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub stale_hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub negative_entries: usize,
+}
+
+impl Cache {
+    pub fn new(capacity: usize, negative_capacity: usize, min_ttl: u32, max_ttl: u32) -> Self {
+        Self {
+            min_ttl,
+            max_ttl: max_ttl.max(min_ttl),
+            max_stale: DEFAULT_MAX_STALE,
+            positive: Mutex::new(Lru::new(capacity)),
+            negative: Mutex::new(Lru::new(negative_capacity)),
+            hits: AtomicU64::new(0),
+            stale_hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    // How long past their expiry entries may still be served. Zero turns serve-stale off.
+    pub fn max_stale(self, max_stale: Duration) -> Self {
+        Self { max_stale, ..self }
+    }
+
+    // The answer for the question, following CNAMEs through the cache. Only a complete
+    // chain, ending in either the RRset asked for or a cached negative result, is a hit.
+    pub fn lookup(&self, q: &Question) -> Option<Cached> {
+        self.lookup_at(q, Instant::now())
+    }
+
+    // Stores a reply from a server trusted for names at or below `bailiwick`.
+    pub fn store(
+        &self,
+        q: &Question,
+        bailiwick: &DomainName,
+        rcode: Rcode,
+        answers: &[Answer],
+        authorities: &[Answer],
+    ) {
+        self.store_at(q, bailiwick, rcode, answers, authorities, Instant::now())
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            stale_hits: self.stale_hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.positive.lock().map_or(0, |lru| lru.entries.len()),
+            negative_entries: self.negative.lock().map_or(0, |lru| lru.entries.len()),
+        }
+    }
+
+    fn lookup_at(&self, q: &Question, now: Instant) -> Option<Cached> {
+        let cached = self.find(q, now);
+
+        match &cached {
+            Some(cached) if cached.stale => self.stale_hits.fetch_add(1, Ordering::Relaxed),
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+
+        cached
+    }
+
+    fn find(&self, q: &Question, now: Instant) -> Option<Cached> {
+        let mut positive = self.positive.lock().ok()?;
+        let mut negative = self.negative.lock().ok()?;
+        let mut name = q.name().clone();
+        let mut answers: Vec<Answer> = vec![];
+        let (mut stale, mut prefetch) = (false, false);
+        let max_stale = self.max_stale;
+
+        for _ in 0..=MAX_CNAMES {
+            let key = (name.clone(), q.r#type(), q.class());
+            if let Some(hit) = positive.get(&key, now, max_stale) {
+                answers.extend(with_ttl(hit.value, hit.ttl));
+                stale |= hit.stale;
+                prefetch |= hit.prefetch;
+                return Some(Cached {
+                    rcode: Rcode::NoErr,
+                    answers,
+                    authorities: vec![],
+                    stale,
+                    refresh: stale || prefetch,
+                });
+            }
+
+            let nodata = (name.clone(), Some(q.r#type()), q.class());
+            let nxdomain = (name.clone(), None, q.class());
+            if let Some(hit) = negative
+                .get(&nodata, now, max_stale)
+                .or_else(|| negative.get(&nxdomain, now, max_stale))
+            {
+                stale |= hit.stale;
+                prefetch |= hit.prefetch;
+                return Some(Cached {
+                    rcode: hit.value.rcode,
+                    answers,
+                    authorities: vec![hit.value.soa.set_ttl(hit.ttl)],
+                    stale,
+                    refresh: stale || prefetch,
+                });
+            }
+
+            if q.r#type() == RecordType::Cname {
+                return None;
+            }
+
+            let key = (name.clone(), RecordType::Cname, q.class());
+            let hit = positive.get(&key, now, max_stale)?;
+
+            match hit.value.first().map(Answer::data) {
+                Some(Rdata::Cname(target)) => name = target.clone(),
+                _ => return None,
+            }
+            answers.extend(with_ttl(hit.value, hit.ttl));
+            stale |= hit.stale;
+            prefetch |= hit.prefetch;
+        }
+
+        None
+    }
+
+    fn store_at(
+        &self,
+        q: &Question,
+        bailiwick: &DomainName,
+        rcode: Rcode,
+        answers: &[Answer],
+        authorities: &[Answer],
+        now: Instant,
+    ) {
+        if !matches!(rcode, Rcode::NoErr | Rcode::NonexistentDomain) {
+            return;
+        }
+
+        // Only records on the chain from the question, and within what the server is
+        // trusted for, are cached; anything else in the reply could be planted.
+        // Ref: https://datatracker.ietf.org/doc/html/rfc2181#section-5.4.1
+        let trusted = |a: &&Answer| a.name().is_subdomain_of(bailiwick);
+
+        // The chain of CNAMEs also decides which name the negative result belongs to.
+        // Ref: https://datatracker.ietf.org/doc/html/rfc2308#section-2.1
+        let mut name = q.name().clone();
+        let mut chain = vec![name.clone()];
+        for _ in 0..=MAX_CNAMES {
+            let target = answers.iter().filter(trusted).find_map(|a| match a.data() {
+                Rdata::Cname(target) if a.name() == &name => Some(target.clone()),
+                _ => None,
+            });
+
+            match target {
+                Some(target) if q.r#type() != RecordType::Cname && !chain.contains(&target) => {
+                    name = target.clone();
+                    chain.push(target);
+                }
+                _ => break,
+            }
+        }
+
+        let records: Vec<Answer> = answers
+            .iter()
+            .filter(trusted)
+            .filter(|a| chain.contains(a.name()))
+            .cloned()
+            .collect();
+        self.store_rrsets_at(&records, now);
+
+        if records
+            .iter()
+            .any(|a| a.name() == &name && a.r#type() == q.r#type())
+        {
+            return;
+        }
+
+        // Without an SOA there is no TTL for the negative result, so it is not cached.
+        // Ref: https://datatracker.ietf.org/doc/html/rfc2308#section-5
+        let Some((soa, minimum)) = authorities.iter().find_map(|a| match a.data() {
+            Rdata::Soa { minimum, .. } if name.is_subdomain_of(a.name()) && trusted(&a) => {
+                Some((a, *minimum))
+            }
+            _ => None,
+        }) else {
+            return;
+        };
+
+        let ttl = self.clamp(soa.ttl().min(minimum)).min(MAX_NEGATIVE_TTL);
+        let r#type = match rcode {
+            Rcode::NonexistentDomain => None,
+            _ => Some(q.r#type()),
+        };
+        let negative = Negative {
+            rcode,
+            soa: soa.clone(),
+        };
+
+        if let (true, Ok(mut lru)) = (ttl > 0, self.negative.lock()) {
+            lru.insert((name, r#type, q.class()), negative, ttl, now);
+        }
+    }
+
+    fn store_rrsets_at(&self, records: &[Answer], now: Instant) {
+        let mut rrsets: HashMap<Key, Vec<Answer>> = HashMap::new();
+        for record in records {
+            let key = (record.name().clone(), record.r#type(), record.class());
+            rrsets.entry(key).or_default().push(record.clone());
+        }
+
+        let Ok(mut lru) = self.positive.lock() else {
+            return;
+        };
+
+        for (key, records) in rrsets {
+            // Records of one RRset should share a TTL; the lowest one wins if they do not.
+            // Ref: https://datatracker.ietf.org/doc/html/rfc2181#section-5.2
+            let ttl = records.iter().map(Answer::ttl).min().unwrap_or(0);
+            let ttl = self.clamp(ttl);
+
+            if ttl > 0 {
+                lru.insert(key, records, ttl, now);
+            }
+        }
+    }
+
+    fn clamp(&self, ttl: u32) -> u32 {
+        ttl.clamp(self.min_ttl, self.max_ttl)
+    }
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_CAPACITY,
+            DEFAULT_NEGATIVE_CAPACITY,
+            DEFAULT_MIN_TTL,
+            DEFAULT_MAX_TTL,
+        )
+    }
+}
+
+fn with_ttl(records: Vec<Answer>, ttl: u32) -> Vec<Answer> {
+    records
+        .into_iter()
+        .map(|record| record.set_ttl(ttl))
+        .collect()
+}
+
+impl<K: Clone + Eq + Hash, V: Clone> Lru<K, V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    // A copy of the value with its remaining TTL, or with `STALE_TTL` once it has expired
+    // but is still within `max_stale` of its expiry.
+    fn get(&mut self, key: &K, now: Instant, max_stale: Duration) -> Option<Hit<V>> {
+        let entry = self.entries.get_mut(key)?;
+
+        if entry.expires_at + max_stale <= now {
+            self.remove(key);
+            return None;
+        }
+
+        entry.hits = entry.hits.saturating_add(1);
+        let hit = if entry.expires_at <= now {
+            Hit {
+                value: entry.value.clone(),
+                ttl: STALE_TTL,
+                stale: true,
+                prefetch: false,
+            }
+        } else {
+            let ttl = (entry.expires_at - now).as_secs() as u32;
+            Hit {
+                value: entry.value.clone(),
+                ttl,
+                stale: false,
+                prefetch: entry.hits >= PREFETCH_MIN_HITS && ttl < entry.ttl / PREFETCH_TTL_DIVISOR,
+            }
+        };
+        self.touch(key);
+        Some(hit)
+    }
+
+    fn insert(&mut self, key: K, value: V, ttl: u32, now: Instant) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.remove(&key);
+
+        while self.entries.len() >= self.capacity {
+            match self.recency.pop_first() {
+                Some((_, oldest)) => self.entries.remove(&oldest),
+                None => break,
+            };
+        }
+
+        self.clock += 1;
+        self.recency.insert(self.clock, key.clone());
+        self.entries.insert(
+            key,
+            Entry {
+                value,
+                ttl,
+                expires_at: now + Duration::from_secs(ttl as u64),
+                hits: 0,
+                used_at: self.clock,
+            },
+        );
+    }
+
+    fn touch(&mut self, key: &K) {
+        self.clock += 1;
+
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.recency.remove(&entry.used_at);
+            self.recency.insert(self.clock, key.clone());
+            entry.used_at = self.clock;
+        }
+    }
+
+    fn remove(&mut self, key: &K) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.used_at);
+        }
+    }
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cache hits={} stale_hits={} misses={} entries={} negative_entries={}",
+            self.hits, self.stale_hits, self.misses, self.entries, self.negative_entries
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn a(name: &str, ttl: u32, last: u8) -> Answer {
+        let data = Rdata::A([10, 0, 0, last]);
+        Answer::record(
+            name.parse().unwrap(),
+            RecordType::A,
+            RecordClass::In,
+            ttl,
+            data,
+        )
+    }
+
+    fn question(name: &str, r#type: RecordType) -> Question {
+        Question::from_parts(name.parse().unwrap(), r#type, RecordClass::In)
+    }
+
+    #[test]
+    fn it_counts_down_ttls_until_expiry() {
+        let cache = Cache::default().max_stale(Duration::ZERO);
+        let now = Instant::now();
+        cache.store_rrsets_at(&[a("a.test", 300, 1), a("a.test", 300, 2)], now);
+
+        let q = question("A.TEST", RecordType::A);
+        let hit = cache
+            .lookup_at(&q, now + Duration::from_secs(100))
+            .unwrap()
+            .answers;
+        assert_eq!(hit.len(), 2);
+        assert_eq!(hit[0].ttl(), 200);
+
+        assert!(cache
+            .lookup_at(&q, now + Duration::from_secs(300))
+            .is_none());
+        assert!(cache
+            .lookup_at(&question("b.test", RecordType::A), now)
+            .is_none());
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 1,
+                stale_hits: 0,
+                misses: 2,
+                entries: 0,
+                negative_entries: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn it_serves_stale_entries_within_the_window() {
+        let cache = Cache::default().max_stale(Duration::from_secs(600));
+        let now = Instant::now();
+        cache.store_rrsets_at(&[a("a.test", 300, 1)], now);
+
+        let q = question("a.test", RecordType::A);
+        let hit = cache.lookup_at(&q, now).unwrap();
+        assert!(!hit.stale && !hit.refresh);
+
+        let hit = cache.lookup_at(&q, now + Duration::from_secs(500)).unwrap();
+        assert!(hit.stale && hit.refresh);
+        assert_eq!(hit.answers[0].ttl(), STALE_TTL);
+        assert_eq!(cache.stats().stale_hits, 1);
+
+        assert!(cache
+            .lookup_at(&q, now + Duration::from_secs(900))
+            .is_none());
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[test]
+    fn it_prefetches_popular_entries_close_to_expiry() {
+        let cache = Cache::default();
+        let now = Instant::now();
+        cache.store_rrsets_at(&[a("hot.test", 300, 1), a("cold.test", 300, 1)], now);
+
+        let hot = question("hot.test", RecordType::A);
+        for _ in 0..PREFETCH_MIN_HITS {
+            assert!(!cache.lookup_at(&hot, now).unwrap().refresh);
+        }
+
+        let later = now + Duration::from_secs(290);
+        assert!(cache.lookup_at(&hot, later).unwrap().refresh);
+        let cold = question("cold.test", RecordType::A);
+        assert!(!cache.lookup_at(&cold, later).unwrap().refresh);
+
+        // a refreshed entry has to become popular again
+        cache.store_rrsets_at(&[a("hot.test", 300, 1)], later);
+        assert!(!cache.lookup_at(&hot, later).unwrap().refresh);
+    }
+
+    #[test]
+    fn it_clamps_ttls() {
+        let cache = Cache::new(10, 10, 60, 3600);
+        let now = Instant::now();
+        cache.store_rrsets_at(&[a("short.test", 1, 1), a("long.test", 604_800, 1)], now);
+
+        let hit = cache.lookup_at(&question("short.test", RecordType::A), now);
+        assert_eq!(hit.unwrap().answers[0].ttl(), 60);
+        let hit = cache.lookup_at(&question("long.test", RecordType::A), now);
+        assert_eq!(hit.unwrap().answers[0].ttl(), 3600);
+    }
+
+    #[test]
+    fn it_evicts_the_least_recently_used_rrset() {
+        let cache = Cache::new(2, 2, 0, 3600);
+        let now = Instant::now();
+        cache.store_rrsets_at(&[a("a.test", 60, 1)], now);
+        cache.store_rrsets_at(&[a("b.test", 60, 1)], now);
+        assert!(cache
+            .lookup_at(&question("a.test", RecordType::A), now)
+            .is_some());
+
+        cache.store_rrsets_at(&[a("c.test", 60, 1)], now);
+        assert!(cache
+            .lookup_at(&question("a.test", RecordType::A), now)
+            .is_some());
+        assert!(cache
+            .lookup_at(&question("b.test", RecordType::A), now)
+            .is_none());
+        assert!(cache
+            .lookup_at(&question("c.test", RecordType::A), now)
+            .is_some());
+    }
+
+    #[test]
+    fn it_follows_cached_cnames() {
+        let cache = Cache::default();
+        let now = Instant::now();
+        let cname = Answer::record(
+            "www.test".parse().unwrap(),
+            RecordType::Cname,
+            RecordClass::In,
+            60,
+            Rdata::Cname("web.test".parse().unwrap()),
+        );
+        cache.store_rrsets_at(&[cname], now);
+
+        let q = question("www.test", RecordType::A);
+        assert!(cache.lookup_at(&q, now).is_none());
+
+        cache.store_rrsets_at(&[a("web.test", 60, 1)], now);
+        let hit = cache.lookup_at(&q, now).unwrap().answers;
+        assert_eq!(hit.len(), 2);
+        assert_eq!(hit[0].r#type(), RecordType::Cname);
+    }
+
+    fn soa(zone: &str, ttl: u32, minimum: u32) -> Answer {
+        let data = Rdata::Soa {
+            mname: "ns.test".parse().unwrap(),
+            rname: "host.test".parse().unwrap(),
+            serial: 1,
+            refresh: 2,
+            retry: 3,
+            expire: 4,
+            minimum,
+        };
+        Answer::record(
+            zone.parse().unwrap(),
+            RecordType::Soa,
+            RecordClass::In,
+            ttl,
+            data,
+        )
+    }
+
+    #[test]
+    fn it_only_stores_the_chain_within_the_bailiwick() {
+        let cache = Cache::default();
+        let now = Instant::now();
+        let cname = Answer::record(
+            "www.corp.test".parse().unwrap(),
+            RecordType::Cname,
+            RecordClass::In,
+            60,
+            Rdata::Cname("web.corp.test".parse().unwrap()),
+        );
+        let answers = [
+            cname,
+            a("web.corp.test", 60, 1),
+            a("unrelated.corp.test", 60, 1),
+            a("victim.example", 60, 1),
+        ];
+        let q = question("www.corp.test", RecordType::A);
+        cache.store_at(&q, &DomainName::root(), Rcode::NoErr, &answers, &[], now);
+
+        assert_eq!(cache.lookup_at(&q, now).unwrap().answers.len(), 2);
+        assert!(cache
+            .lookup_at(&question("unrelated.corp.test", RecordType::A), now)
+            .is_none());
+        assert!(cache
+            .lookup_at(&question("victim.example", RecordType::A), now)
+            .is_none());
+
+        // a corp.test server answering for a name outside its zone
+        let q = question("victim.example", RecordType::A);
+        let bailiwick = "corp.test".parse().unwrap();
+        let answers = [a("victim.example", 60, 1)];
+        cache.store_at(&q, &bailiwick, Rcode::NoErr, &answers, &[], now);
+        assert_eq!(cache.stats().entries, 2);
+    }
+
+    #[test]
+    fn it_caches_nxdomain_for_every_type() {
+        let cache = Cache::default().max_stale(Duration::ZERO);
+        let now = Instant::now();
+        let q = question("typo.test", RecordType::A);
+        cache.store_at(
+            &q,
+            &DomainName::root(),
+            Rcode::NonexistentDomain,
+            &[],
+            &[soa("test", 3600, 300)],
+            now,
+        );
+
+        let later = now + Duration::from_secs(100);
+        let hit = cache
+            .lookup_at(&question("typo.test", RecordType::Mx), later)
+            .unwrap();
+        assert_eq!(hit.rcode, Rcode::NonexistentDomain);
+        assert!(hit.answers.is_empty());
+        assert_eq!(hit.authorities[0].r#type(), RecordType::Soa);
+        assert_eq!(hit.authorities[0].ttl(), 200);
+
+        assert!(cache
+            .lookup_at(&q, now + Duration::from_secs(300))
+            .is_none());
+    }
+
+    #[test]
+    fn it_caches_nodata_for_one_type() {
+        let cache = Cache::default();
+        let now = Instant::now();
+        let q = question("v4only.test", RecordType::Aaaa);
+        cache.store_at(
+            &q,
+            &DomainName::root(),
+            Rcode::NoErr,
+            &[],
+            &[soa("test", 60, 300)],
+            now,
+        );
+
+        let hit = cache.lookup_at(&q, now).unwrap();
+        assert_eq!(hit.rcode, Rcode::NoErr);
+        assert_eq!(hit.authorities[0].ttl(), 60);
+        assert!(cache
+            .lookup_at(&question("v4only.test", RecordType::A), now)
+            .is_none());
+
+        // not cached without an SOA, nor with the SOA of an unrelated zone
+        let q = question("other.test", RecordType::Aaaa);
+        cache.store_at(&q, &DomainName::root(), Rcode::NoErr, &[], &[], now);
+        cache.store_at(
+            &q,
+            &DomainName::root(),
+            Rcode::NoErr,
+            &[],
+            &[soa("example", 60, 300)],
+            now,
+        );
+        assert!(cache.lookup_at(&q, now).is_none());
+    }
+
+    #[test]
+    fn it_limits_negative_entries_separately() {
+        let cache = Cache::new(10, 1, 0, 3600);
+        let now = Instant::now();
+        let authorities = [soa("test", 60, 60)];
+
+        for name in ["a.test", "b.test"] {
+            let q = question(name, RecordType::A);
+            cache.store_at(
+                &q,
+                &DomainName::root(),
+                Rcode::NonexistentDomain,
+                &[],
+                &authorities,
+                now,
+            );
+        }
+        cache.store_rrsets_at(&[a("c.test", 60, 1)], now);
+
+        assert!(cache
+            .lookup_at(&question("a.test", RecordType::A), now)
+            .is_none());
+        assert!(cache
+            .lookup_at(&question("b.test", RecordType::A), now)
+            .is_some());
+        assert_eq!(cache.stats().entries, 1);
+        assert_eq!(cache.stats().negative_entries, 1);
+    }
+}
@@ -0,0 +1,177 @@
+use super::{recursor::DNS_PORT, upstream::Upstream, DomainName, Result};
+use crate::Error;
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::str::FromStr;
+
+// Queries for names at or below `suffix` go to the rule's own upstreams instead of the
+// default ones.
+#[derive(Debug)]
+pub struct ForwardRule {
+    suffix: DomainName,
+    upstreams: Vec<Upstream>,
+    fallback: Fallback,
+}
+
+// What a query gets once none of the rule's upstreams answered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Fallback {
+    #[default]
+    ServFail,
+    Recurse,
+    Refuse,
+}
+
+impl ForwardRule {
+    pub fn suffix(&self) -> &DomainName {
+        &self.suffix
+    }
+
+    pub fn upstreams(&self) -> &[Upstream] {
+        &self.upstreams
+    }
+
+    pub fn fallback(&self) -> Fallback {
+        self.fallback
+    }
+}
+
+// `SUFFIX=ADDR[,ADDR...][/recurse|/refuse]`, where an address without a port is on port 53.
+// A rule may leave out the addresses to always take its fallback.
+impl FromStr for ForwardRule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (suffix, rest) = s
+            .split_once('=')
+            .ok_or(err!("Forwarding rule {s} has no '='"))?;
+
+        let (addrs, fallback) = match rest.rsplit_once('/') {
+            Some((addrs, "recurse")) => (addrs, Fallback::Recurse),
+            Some((addrs, "refuse")) => (addrs, Fallback::Refuse),
+            Some((_, other)) => {
+                return Err(err!("Forwarding rule {s} has unknown fallback {other}"))
+            }
+            None => (rest, Fallback::ServFail),
+        };
+
+        let mut upstreams: Vec<Upstream> = vec![];
+        for addr in addrs.split(',').map(str::trim).filter(|a| !a.is_empty()) {
+            upstreams.push(Upstream::new(parse_addr(addr)?));
+        }
+
+        if upstreams.is_empty() && fallback == Fallback::ServFail {
+            return Err(err!(
+                "Forwarding rule {s} has neither upstreams nor a fallback"
+            ));
+        }
+
+        Ok(Self {
+            suffix: suffix.trim().parse()?,
+            upstreams,
+            fallback,
+        })
+    }
+}
+
+fn parse_addr(s: &str) -> Result<SocketAddr> {
+    s.parse::<SocketAddr>()
+        .or_else(|_| s.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, DNS_PORT)))
+        .map_err(|_| err!("Invalid upstream address {s}"))
+}
+
+// One rule per line. Blank lines and anything after a `#` are ignored.
+pub fn read_forward_rules<P: AsRef<Path>>(path: P) -> Result<Vec<ForwardRule>> {
+    let mut rules: Vec<ForwardRule> = vec![];
+
+    for (n, line) in fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+
+        if !line.is_empty() {
+            rules.push(line.parse().map_err(|e| err!("line {}: {e}", n + 1))?);
+        }
+    }
+
+    Ok(rules)
+}
+
+// The rule with the longest suffix the name falls under. Of two rules for the same suffix
+// the later one wins.
+pub fn route<'a>(rules: &'a [ForwardRule], name: &DomainName) -> Option<&'a ForwardRule> {
+    rules
+        .iter()
+        .filter(|rule| name.is_subdomain_of(&rule.suffix))
+        .max_by_key(|rule| rule.suffix.labels().len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(s: &str) -> DomainName {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn it_parses_rules() {
+        let rule: ForwardRule = "corp.example=10.0.0.53,10.0.0.54:5353/recurse"
+            .parse()
+            .unwrap();
+        assert_eq!(rule.suffix, name("corp.example"));
+        let addrs: Vec<SocketAddr> = rule.upstreams().iter().map(Upstream::addr).collect();
+        assert_eq!(
+            addrs,
+            vec![
+                "10.0.0.53:53".parse().unwrap(),
+                "10.0.0.54:5353".parse().unwrap()
+            ]
+        );
+        assert_eq!(rule.fallback(), Fallback::Recurse);
+
+        let rule: ForwardRule = "10.in-addr.arpa=[::1]:53".parse().unwrap();
+        assert_eq!(rule.fallback(), Fallback::ServFail);
+        let rule: ForwardRule = "ads.example=/refuse".parse().unwrap();
+        assert!(rule.upstreams().is_empty());
+
+        assert!("corp.example".parse::<ForwardRule>().is_err());
+        assert!("corp.example=".parse::<ForwardRule>().is_err());
+        assert!("corp.example=10.0.0.53/drop"
+            .parse::<ForwardRule>()
+            .is_err());
+        assert!("corp.example=dns.corp".parse::<ForwardRule>().is_err());
+    }
+
+    #[test]
+    fn it_routes_by_the_longest_suffix() {
+        let rules: Vec<ForwardRule> = ["example=10.0.0.1", "corp.example=10.0.0.2", ".=10.0.0.3"]
+            .iter()
+            .map(|rule| rule.parse().unwrap())
+            .collect();
+
+        let suffix = |s: &str| route(&rules, &name(s)).map(|rule| rule.suffix.clone());
+        assert_eq!(suffix("WWW.Corp.Example"), Some(name("corp.example")));
+        assert_eq!(suffix("corp.example"), Some(name("corp.example")));
+        assert_eq!(suffix("othercorp.example"), Some(name("example")));
+        assert_eq!(suffix("example.com"), Some(name(".")));
+        assert!(route(&rules[..2], &name("example.com")).is_none());
+    }
+
+    #[test]
+    fn it_reads_rules_from_a_file() {
+        let path = std::env::temp_dir().join(format!("forward-rules-{}", std::process::id()));
+        fs::write(
+            &path,
+            "# internal zones\ncorp.example=10.0.0.53 # primary\n\n10.in-addr.arpa=/refuse\n",
+        )
+        .unwrap();
+        let rules = read_forward_rules(&path).unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[1].fallback(), Fallback::Refuse);
+
+        fs::write(&path, "corp.example=10.0.0.53\ncorp.example\n").unwrap();
+        let err = read_forward_rules(&path).unwrap_err();
+        assert!(err.to_string().contains("line 2"));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,1091 @@
+use super::{
+    utils,
+    zone::{self, Zone},
+    Answer, DomainName, Edns, EdnsOption, Message, OpCode, Question, Rcode, Rdata, RecordClass,
+    RecordType, Result, MAX_CNAMES, MAX_UDP_PAYLOAD_SIZE,
+};
+use forwarding::Fallback;
+use std::collections::HashSet;
+use std::io::{ErrorKind, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use upstream::Upstream;
+
+pub use cache::Cache;
+pub use forwarding::{read_forward_rules, ForwardRule};
+
+mod cache;
+mod forwarding;
+mod recursor;
+mod upstream;
+
+// The first round of queries waits this long for each upstream, and every following round
+// doubles the wait up to `MAX_TIMEOUT`.
+const INITIAL_TIMEOUT: Duration = Duration::from_millis(500);
+const MAX_TIMEOUT: Duration = Duration::from_secs(4);
+const MAX_ROUNDS: usize = 3;
+// Attempts at binding a random source port before leaving the choice to the kernel.
+const MAX_BIND_ATTEMPTS: usize = 8;
+// Ports below this one are privileged or registered for well known services.
+const MIN_SOURCE_PORT: u16 = 1024;
+// Cache refreshes waiting for `refresh_cache`. Further ones are dropped until it catches up,
+// and the stale or expiring entries ask for them again on their next lookup.
+const REFRESH_QUEUE_SIZE: usize = 64;
+
+#[derive(Debug)]
+pub struct Resolver {
+    upstreams: Vec<Upstream>,
+    forward_rules: Vec<ForwardRule>,
+    zones: Vec<Zone>,
+    root_hints: Vec<SocketAddr>,
+    // Port of the nameservers learned from referrals. Only tests, whose fake nameservers
+    // cannot listen on the standard port, change it.
+    nameserver_port: u16,
+    cache: Cache,
+    identity: Identity,
+    case_randomization: bool,
+    refreshes: SyncSender<Question>,
+    refresh_queue: Mutex<Receiver<Question>>,
+    // Questions queued or being refreshed, so that each is only resolved once at a time.
+    refreshing: Mutex<HashSet<Question>>,
+}
+
+impl Resolver {
+    pub fn new(addrs: Vec<SocketAddr>) -> Self {
+        let (refreshes, refresh_queue) = mpsc::sync_channel(REFRESH_QUEUE_SIZE);
+
+        Self {
+            upstreams: addrs.into_iter().map(Upstream::new).collect(),
+            forward_rules: vec![],
+            zones: vec![],
+            root_hints: recursor::root_hints(),
+            nameserver_port: recursor::DNS_PORT,
+            cache: Cache::default(),
+            identity: Identity::default(),
+            case_randomization: false,
+            refreshes,
+            refresh_queue: Mutex::new(refresh_queue),
+            refreshing: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn identity(self, identity: Identity) -> Self {
+        Self { identity, ..self }
+    }
+
+    pub fn case_randomization(self, case_randomization: bool) -> Self {
+        Self {
+            case_randomization,
+            ..self
+        }
+    }
+
+    pub fn forward_rules(self, forward_rules: Vec<ForwardRule>) -> Self {
+        Self {
+            forward_rules,
+            ..self
+        }
+    }
+
+    pub fn zones(self, zones: Vec<Zone>) -> Self {
+        Self { zones, ..self }
+    }
+
+    pub fn cache(self, cache: Cache) -> Self {
+        Self { cache, ..self }
+    }
+
+    pub fn cache_stats(&self) -> cache::CacheStats {
+        self.cache.stats()
+    }
+
+    // Servers recursion starts from when no upstream is configured.
+    pub fn root_hints(self, root_hints: Vec<SocketAddr>) -> Self {
+        if root_hints.is_empty() {
+            return self;
+        }
+
+        Self { root_hints, ..self }
+    }
+
+    // The reply to an incoming message, or none for a message that is a response itself or
+    // too short to even carry an ID the reply could be matched by.
+    pub fn resolve(&self, buf: &[u8]) -> Result<Option<Message>> {
+        let msg = match Message::try_from(buf) {
+            Ok(msg) => msg,
+            Err(err) => {
+                eprintln!("Cannot parse incoming message: {err}");
+                return Ok(match Message::salvage(buf) {
+                    Some(msg) if !msg.header.is_response() => {
+                        Some(Message::reply(msg).set_rcode(Rcode::FormatErr))
+                    }
+                    _ => None,
+                });
+            }
+        };
+
+        // Answering responses could make two servers bounce packets off each other forever.
+        if msg.header.is_response() {
+            return Ok(None);
+        }
+
+        self.reply(msg).map(Some)
+    }
+
+    fn reply(&self, msg: Message) -> Result<Message> {
+        if msg.edns.as_ref().is_some_and(|edns| edns.version() > 0) {
+            return Ok(Message::reply(msg).set_rcode(Rcode::BadVers));
+        }
+
+        if !matches!(msg.header.opcode(), OpCode::Query) {
+            return Ok(Message::reply(msg).set_rcode(Rcode::NotImplemented));
+        }
+
+        if msg
+            .questions
+            .iter()
+            .any(|q| !matches!(q.class(), RecordClass::In | RecordClass::Ch))
+        {
+            return Ok(Message::reply(msg).set_rcode(Rcode::Refused));
+        }
+
+        let nsid_requested = msg.edns.as_ref().is_some_and(|edns| {
+            edns.options()
+                .iter()
+                .any(|option| option.code == EdnsOption::NSID)
+        });
+
+        let checking_disabled = msg.header.cd();
+        let mut reply_msg = Message::reply(msg);
+        let dnssec_ok = reply_msg.edns.as_ref().is_some_and(Edns::dnssec_ok);
+        let recursion_desired = reply_msg.header.rd();
+
+        if let (true, Some(edns), Some(nsid)) =
+            (nsid_requested, reply_msg.edns.clone(), self.identity.nsid())
+        {
+            reply_msg = reply_msg.set_edns(edns.set_option(nsid));
+        }
+        let mut answers: Vec<Answer> = vec![];
+        let mut authorities: Vec<Answer> = vec![];
+        let mut additionals: Vec<Answer> = vec![];
+
+        // With several questions the reply only claims a flag that every upstream reply
+        // set, and carries the first error code among them.
+        let mut resolved = false;
+        let (mut aa, mut ra, mut ad) = (true, true, true);
+        let mut tc = false;
+        let mut rcode = Rcode::NoErr;
+
+        for q in reply_msg.questions.iter() {
+            // A zone of class CH takes precedence over the built-in identity.
+            let result = if let Some(zone) = zone::find(&self.zones, q) {
+                Ok(authoritative_reply(q, zone.lookup(q)))
+            } else if q.class() == RecordClass::Ch {
+                answers.extend(self.identity.answer(q));
+                continue;
+            } else {
+                // Stale and soon expiring entries are answered right away and brought up to
+                // date in the background.
+                // Ref: https://datatracker.ietf.org/doc/html/rfc8767#section-5
+                match self.cache.lookup(q) {
+                    Some(cached) => {
+                        if cached.refresh {
+                            self.schedule_refresh(q);
+                        }
+                        Ok(recursive_reply(
+                            q,
+                            cached.rcode,
+                            cached.answers,
+                            cached.authorities,
+                        ))
+                    }
+                    None => {
+                        let edns = Edns::new(MAX_UDP_PAYLOAD_SIZE).set_dnssec_ok(dnssec_ok);
+                        self.fetch(q, edns, recursion_desired, checking_disabled)
+                    }
+                }
+            };
+
+            let mut resolve_msg = match result {
+                Ok(msg) => msg,
+                Err(err) => {
+                    eprintln!("Cannot resolve {}: {err}", q.name());
+                    return Ok(reply_msg.set_rcode(Rcode::ServerErr));
+                }
+            };
+
+            let header = &resolve_msg.header;
+            resolved = true;
+            aa &= header.aa();
+            ra &= header.ra();
+            ad &= header.ad();
+            tc |= header.tc();
+            if rcode == Rcode::NoErr {
+                rcode = relayed_rcode(header.rcode());
+            }
+
+            answers.append(&mut resolve_msg.answers);
+            authorities.append(&mut resolve_msg.authorities);
+            additionals.append(&mut resolve_msg.additionals);
+        }
+
+        if resolved {
+            reply_msg.header = reply_msg.header.set_aa(aa).set_ra(ra).set_ad(ad).set_tc(tc);
+            reply_msg = reply_msg.set_rcode(rcode);
+        }
+
+        for answer in answers {
+            reply_msg = reply_msg.set_answer(answer);
+        }
+
+        for authority in authorities {
+            reply_msg = reply_msg.set_authority(authority);
+        }
+
+        for additional in additionals {
+            reply_msg = reply_msg.set_additional(additional);
+        }
+
+        Ok(reply_msg)
+    }
+
+    // Resolves queued cache refreshes one after another. Blocks for as long as the resolver
+    // lives, so it is meant to run on a thread of its own.
+    pub fn refresh_cache(&self) {
+        loop {
+            let q = match self.refresh_queue.lock() {
+                Ok(queue) => queue.recv(),
+                Err(_) => return,
+            };
+
+            match q {
+                Ok(q) => self.refresh(&q),
+                Err(_) => return,
+            }
+        }
+    }
+
+    fn schedule_refresh(&self, q: &Question) {
+        let Ok(mut refreshing) = self.refreshing.lock() else {
+            return;
+        };
+
+        if refreshing.insert(q.clone()) && self.refreshes.try_send(q.clone()).is_err() {
+            refreshing.remove(q);
+        }
+    }
+
+    fn refresh(&self, q: &Question) {
+        if let Err(err) = self.fetch(q, Edns::new(MAX_UDP_PAYLOAD_SIZE), true, false) {
+            eprintln!("Cannot refresh {}: {err}", q.name());
+        }
+
+        if let Ok(mut refreshing) = self.refreshing.lock() {
+            refreshing.remove(q);
+        }
+    }
+
+    // Resolves the question through the upstreams of the matching forwarding rule, else the
+    // default upstreams, or recursively without any, and caches the result.
+    fn fetch(&self, q: &Question, edns: Edns, rd: bool, cd: bool) -> Result<Message> {
+        // The upstreams of a rule are only trusted for names under its suffix.
+        let (msg, bailiwick) = match forwarding::route(&self.forward_rules, q.name()) {
+            Some(rule) => match self.exchange(rule.upstreams(), q, edns, rd, cd) {
+                Ok(msg) => (msg, rule.suffix().clone()),
+                Err(err) => match rule.fallback() {
+                    Fallback::ServFail => return Err(err),
+                    Fallback::Recurse => (self.recurse(q)?, DomainName::root()),
+                    Fallback::Refuse => (
+                        recursive_reply(q, Rcode::Refused, vec![], vec![]),
+                        DomainName::root(),
+                    ),
+                },
+            },
+            None if self.upstreams.is_empty() => (self.recurse(q)?, DomainName::root()),
+            None => (
+                self.exchange(&self.upstreams, q, edns, rd, cd)?,
+                DomainName::root(),
+            ),
+        };
+
+        // A reply that is still truncated is incomplete and only good for this one client.
+        if !msg.header.tc() {
+            let (answers, authorities) = (&msg.answers, &msg.authorities);
+            self.cache
+                .store(q, &bailiwick, msg.header.rcode(), answers, authorities);
+        }
+        Ok(msg)
+    }
+
+    // Tries the upstreams one after another, starting over with a longer timeout after a
+    // round in which none of them answered. Each attempt is a new query with its own ID.
+    fn exchange(
+        &self,
+        upstreams: &[Upstream],
+        q: &Question,
+        edns: Edns,
+        rd: bool,
+        cd: bool,
+    ) -> Result<Message> {
+        let mut timeout = INITIAL_TIMEOUT;
+
+        for _ in 0..MAX_ROUNDS {
+            for upstream in upstream::candidates(upstreams) {
+                let mut query = self.outgoing_query(q)?.set_edns(edns.clone());
+                query.header = query.header.set_rd(rd).set_cd(cd);
+
+                match self.forward(upstream.addr(), &query, timeout) {
+                    Ok(reply) => {
+                        upstream.succeeded();
+                        return Ok(restore_case(reply, q));
+                    }
+                    Err(err) => {
+                        eprintln!("Upstream {} failed: {err}", upstream.addr());
+                        upstream.failed();
+                    }
+                }
+            }
+
+            timeout = (timeout * 2).min(MAX_TIMEOUT);
+        }
+
+        Err(err!("No upstream answered"))
+    }
+
+    // A query with a random ID, and a randomized name case when enabled.
+    fn outgoing_query(&self, q: &Question) -> Result<Message> {
+        let q = if self.case_randomization {
+            q.clone().set_name(randomize_case(q.name())?)
+        } else {
+            q.clone()
+        };
+
+        Ok(Message::query(utils::random_u16()?, &q))
+    }
+
+    // Sends the query from a fresh socket on a random port and waits for a reply that
+    // matches it. The socket is connected, so the kernel already drops datagrams from other
+    // sources; anything else that does not answer our ID and question is discarded as well.
+    fn forward(&self, upstream: SocketAddr, query: &Message, timeout: Duration) -> Result<Message> {
+        let socket = bind_random_port(upstream)?;
+        socket.connect(upstream)?;
+        socket.send(&query.as_bytes())?;
+
+        let deadline = Instant::now() + timeout;
+        let mut buf = [0u8; MAX_UDP_PAYLOAD_SIZE as usize];
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(err!("Upstream {upstream} did not answer in time"));
+            }
+            socket.set_read_timeout(Some(remaining))?;
+
+            let size = match socket.recv(&mut buf) {
+                Ok(size) => size,
+                Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    continue
+                }
+                Err(err) => return Err(err.into()),
+            };
+
+            match Message::try_from(&buf[..size]) {
+                Ok(reply) if self.answers(&reply, query) && reply.header.tc() => {
+                    // The whole reply has to come over TCP. Failing that, the truncated one
+                    // is used as it is, still marked as truncated.
+                    // Ref: https://datatracker.ietf.org/doc/html/rfc7766#section-5
+                    return match self.forward_tcp(upstream, query, timeout) {
+                        Ok(reply) => Ok(reply),
+                        Err(err) => {
+                            eprintln!("Upstream {upstream} failed over TCP: {err}");
+                            Ok(reply)
+                        }
+                    };
+                }
+                Ok(reply) if self.answers(&reply, query) => return Ok(reply),
+                _ => eprintln!("Discarding unexpected packet from upstream {upstream}"),
+            }
+        }
+    }
+
+    // Sends the query over TCP, framed by its length as a two byte field.
+    // Ref: https://datatracker.ietf.org/doc/html/rfc7766#section-8
+    fn forward_tcp(
+        &self,
+        upstream: SocketAddr,
+        query: &Message,
+        timeout: Duration,
+    ) -> Result<Message> {
+        let mut stream = TcpStream::connect_timeout(&upstream, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+
+        let bytes = query.as_bytes();
+        let len = (bytes.len() as u16).to_be_bytes();
+        stream.write_all(&[&len[..], &bytes].concat())?;
+
+        let len = u16::from_be_bytes(utils::read_2_bytes(&mut stream)?);
+        let reply = Message::try_from(utils::read_n_bytes(&mut stream, len as usize)?.as_slice())?;
+
+        if !self.answers(&reply, query) {
+            return Err(err!(
+                "Upstream {upstream} sent an unexpected reply over TCP"
+            ));
+        }
+
+        Ok(reply)
+    }
+
+    // With case randomization the question must also come back with the exact same case.
+    fn answers(&self, reply: &Message, query: &Message) -> bool {
+        reply.header.is_response()
+            && reply.id() == query.id()
+            && reply.questions == query.questions
+            && (!self.case_randomization || same_case(reply, query))
+    }
+}
+
+// A reply for one question that this server resolved itself rather than relaying it, as
+// is the case for recursion and cached answers.
+fn recursive_reply(
+    q: &Question,
+    rcode: Rcode,
+    answers: Vec<Answer>,
+    authorities: Vec<Answer>,
+) -> Message {
+    let mut msg = Message::reply(Message::query(0, q)).set_rcode(rcode);
+    msg.header = msg.header.set_ra(true);
+
+    for answer in answers {
+        msg = msg.set_answer(answer);
+    }
+
+    for authority in authorities {
+        msg = msg.set_authority(authority);
+    }
+
+    msg
+}
+
+// Answers from our own zones are authoritative, except for referrals to subzones.
+fn authoritative_reply(q: &Question, lookup: zone::Lookup) -> Message {
+    let mut msg = recursive_reply(q, lookup.rcode, lookup.answers, lookup.authorities);
+    msg.header = msg.header.set_aa(lookup.authoritative);
+
+    for additional in lookup.additionals {
+        msg = msg.set_additional(additional);
+    }
+
+    msg
+}
+
+// Extended RCODEs of an upstream reply concern our own exchange with it, such as the
+// EDNS version or cookies, and mean nothing to the client.
+fn relayed_rcode(rcode: Rcode) -> Rcode {
+    if rcode.extended_bits() == 0 {
+        rcode
+    } else {
+        Rcode::ServerErr
+    }
+}
+
+// Ref: https://datatracker.ietf.org/doc/html/rfc5452#section-9.2
+fn bind_random_port(upstream: SocketAddr) -> Result<UdpSocket> {
+    let ip: IpAddr = match upstream {
+        SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+
+    for _ in 0..MAX_BIND_ATTEMPTS {
+        let port = MIN_SOURCE_PORT + utils::random_u16()? % (u16::MAX - MIN_SOURCE_PORT);
+
+        match UdpSocket::bind((ip, port)) {
+            Ok(socket) => return Ok(socket),
+            Err(err) if err.kind() == ErrorKind::AddrInUse => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Ok(UdpSocket::bind((ip, 0))?)
+}
+
+// Flips the case of every letter at random, adding entropy an off-path attacker has to
+// guess on top of the ID and source port.
+// Ref: https://datatracker.ietf.org/doc/html/draft-vixie-dnsext-dns0x20-00
+fn randomize_case(name: &DomainName) -> Result<DomainName> {
+    let length = name.labels().iter().map(Vec::len).sum();
+    let mut bits = utils::random_bytes(length)?.into_iter();
+
+    let labels = name
+        .labels()
+        .iter()
+        .map(|label| {
+            label
+                .iter()
+                .zip(bits.by_ref())
+                .map(|(b, bit)| match bit & 1 {
+                    0 => b.to_ascii_lowercase(),
+                    _ => b.to_ascii_uppercase(),
+                })
+                .collect()
+        })
+        .collect();
+
+    DomainName::from_labels(labels)
+}
+
+// Names in a reply to a randomized query go back to the case the question was asked in,
+// before they reach the requestor or the cache.
+fn restore_case(mut reply: Message, q: &Question) -> Message {
+    let restore = |records: Vec<Answer>| -> Vec<Answer> {
+        records
+            .into_iter()
+            .map(|record| match record.name() == q.name() {
+                true => record.set_name(q.name().clone()),
+                false => record,
+            })
+            .collect()
+    };
+
+    reply.questions = vec![q.clone()];
+    reply.answers = restore(reply.answers);
+    reply.authorities = restore(reply.authorities);
+    reply.additionals = restore(reply.additionals);
+    reply
+}
+
+fn same_case(reply: &Message, query: &Message) -> bool {
+    reply
+        .questions
+        .iter()
+        .zip(query.questions.iter())
+        .all(|(a, b)| a.name().labels() == b.name().labels())
+}
+
+// Server identity answered for CHAOS class TXT queries.
+// Ref: https://datatracker.ietf.org/doc/html/rfc4892
+#[derive(Debug, Default)]
+pub struct Identity {
+    version: Option<String>,
+    hostname: Option<String>,
+}
+
+impl Identity {
+    pub fn new(version: Option<String>, hostname: Option<String>) -> Self {
+        Self { version, hostname }
+    }
+
+    fn nsid(&self) -> Option<EdnsOption> {
+        self.hostname.as_ref().map(|hostname| EdnsOption {
+            code: EdnsOption::NSID,
+            data: hostname.as_bytes().to_vec(),
+        })
+    }
+
+    fn answer(&self, q: &Question) -> Option<Answer> {
+        if q.r#type() != RecordType::Txt {
+            return None;
+        }
+
+        let value = match q.name().to_string().to_ascii_lowercase().as_str() {
+            "version.bind." | "version.server." => self.version.as_ref(),
+            "hostname.bind." | "id.server." => self.hostname.as_ref(),
+            _ => None,
+        }?;
+
+        Some(Answer::txt(q, 0, value.as_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    #[test]
+    fn it_answers_chaos_identity_queries() {
+        let resolver = Resolver::new(vec![]).identity(Identity::new(Some("cds-0.1".into()), None));
+        let query = b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07VERSION\x04bind\x00\x00\x10\x00\x03";
+
+        let reply = resolver.resolve(query).unwrap().unwrap();
+        assert_eq!(reply.answers.len(), 1);
+        assert!(reply
+            .as_bytes()
+            .ends_with(b"\x00\x10\x00\x03\x00\x00\x00\x00\x00\x08\x07cds-0.1"));
+
+        let query = b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x08hostname\x04bind\x00\x00\x10\x00\x03";
+        let reply = resolver.resolve(query).unwrap().unwrap();
+        assert!(reply.answers.is_empty());
+    }
+
+    fn a_record(q: &Question) -> Answer {
+        let data = Rdata::A([8, 8, 8, 8]);
+        Answer::record(q.name().clone(), RecordType::A, q.class(), 60, data)
+    }
+
+    // A fake upstream on loopback replying to each query with whatever `respond` makes of
+    // it, if anything, for as long as the test runs.
+    fn upstream<F>(respond: F) -> SocketAddr
+    where
+        F: Fn(Message) -> Option<Message> + Send + 'static,
+    {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+
+        thread::spawn(move || {
+            let mut buf = [0u8; 512];
+
+            while let Ok((size, from)) = socket.recv_from(&mut buf) {
+                let query = Message::try_from(&buf[..size]).unwrap();
+                if let Some(reply) = respond(query) {
+                    socket.send_to(&reply.as_bytes(), from).unwrap();
+                }
+            }
+        });
+
+        addr
+    }
+
+    // Replies to the first query only, as if the upstream went away afterwards.
+    fn once<F>(respond: F) -> impl Fn(Message) -> Option<Message> + Send + 'static
+    where
+        F: Fn(Message) -> Message + Send + 'static,
+    {
+        let answered = AtomicBool::new(false);
+        move |query| (!answered.swap(true, Ordering::SeqCst)).then(|| respond(query))
+    }
+
+    fn lookup(resolver: &Resolver, name: &str, r#type: RecordType, class: RecordClass) -> Message {
+        let q = Question::from_parts(name.parse().unwrap(), r#type, class);
+        resolver
+            .resolve(&Message::query(1, &q).as_bytes())
+            .unwrap()
+            .unwrap()
+    }
+
+    fn rcode(msg: &Message) -> u8 {
+        msg.as_bytes()[3] & 0b00001111
+    }
+
+    #[test]
+    fn it_drops_responses() {
+        let resolver = Resolver::new(vec![]).identity(Identity::new(Some("cds-0.1".into()), None));
+        let reply = b"\x12\x34\x81\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07VERSION\x04bind\x00\x00\x10\x00\x03";
+        assert!(resolver.resolve(reply).unwrap().is_none());
+
+        // even when the rest of it does not parse
+        assert!(resolver.resolve(&reply[..16]).unwrap().is_none());
+    }
+
+    #[test]
+    fn it_rejects_bad_queries_with_the_original_id() {
+        let resolver = Resolver::new(vec![]);
+
+        // question claims a 7 byte label but the packet ends early
+        let query = b"\xbe\xef\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07exa";
+        let reply = resolver.resolve(query).unwrap().unwrap();
+        assert_eq!(reply.id(), 0xbeef);
+        assert_eq!(rcode(&reply), 1);
+
+        // answer section is truncated but the question parses and is echoed
+        let query =
+            b"\xbe\xef\x01\x00\x00\x01\x00\x01\x00\x00\x00\x00\x01a\x00\x00\x01\x00\x01\xc0";
+        let reply = resolver.resolve(query).unwrap().unwrap();
+        assert_eq!(rcode(&reply), 1);
+        assert_eq!(reply.questions.len(), 1);
+
+        // STATUS opcode
+        let query = b"\xbe\xef\x11\x00\x00\x01\x00\x00\x00\x00\x00\x00\x01a\x00\x00\x01\x00\x01";
+        let reply = resolver.resolve(query).unwrap().unwrap();
+        assert_eq!(reply.id(), 0xbeef);
+        assert_eq!(rcode(&reply), 4);
+        assert!(reply.answers.is_empty());
+
+        // HS class
+        let query = b"\xbe\xef\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x01a\x00\x00\x01\x00\x04";
+        let reply = resolver.resolve(query).unwrap().unwrap();
+        assert_eq!(rcode(&reply), 5);
+        assert_eq!(reply.questions.len(), 1);
+
+        // too short for a header
+        assert!(resolver.resolve(b"\xbe\xef\x01").unwrap().is_none());
+    }
+
+    #[test]
+    fn it_handles_edns_versions_and_nsid() {
+        let resolver = Resolver::new(vec![]).identity(Identity::new(None, Some("ns1".into())));
+
+        // EDNS version 1
+        let query = b"\x00\x07\x01\x00\x00\x01\x00\x00\x00\x00\x00\x01\x01a\x00\x00\x01\x00\x01\x00\x00\x29\x04\xd0\x00\x01\x00\x00\x00\x00";
+        let reply = resolver.resolve(query).unwrap().unwrap();
+        assert_eq!(reply.header.rcode(), Rcode::BadVers);
+        assert!(reply
+            .as_bytes()
+            .ends_with(b"\x00\x00\x29\x10\x00\x01\x00\x00\x00\x00\x00"));
+
+        // EDNS version 0 asking for NSID
+        let query = b"\x00\x07\x01\x00\x00\x01\x00\x00\x00\x00\x00\x01\x01a\x00\x00\x10\x00\x03\x00\x00\x29\x04\xd0\x00\x00\x00\x00\x00\x04\x00\x03\x00\x00";
+        let reply = resolver.resolve(query).unwrap().unwrap();
+        assert_eq!(reply.header.rcode(), Rcode::NoErr);
+        assert_eq!(reply.udp_payload_size(), 1232);
+        assert!(reply.as_bytes().ends_with(b"\x00\x07\x00\x03\x00\x03ns1"));
+    }
+
+    #[test]
+    fn it_discards_stray_upstream_packets() {
+        let upstream = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let spoofer = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let resolver = Resolver::new(vec![upstream.local_addr().unwrap()]);
+
+        thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let (size, from) = upstream.recv_from(&mut buf).unwrap();
+            let reply = |answers: usize| {
+                let query = Message::try_from(&buf[..size]).unwrap();
+                let q = query.questions[0].clone();
+                (0..answers)
+                    .fold(Message::reply(query), |msg, _| msg.set_answer(a_record(&q)))
+                    .as_bytes()
+            };
+
+            // matching reply sent from another address
+            spoofer.send_to(&reply(3), from).unwrap();
+            // wrong transaction ID
+            let mut stray = reply(2);
+            stray[0] ^= 0xff;
+            upstream.send_to(&stray, from).unwrap();
+
+            upstream.send_to(&reply(1), from).unwrap();
+        });
+
+        let query = b"\x00\x09\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00\x00\x01\x00\x01";
+        let reply = resolver.resolve(query).unwrap().unwrap();
+        assert_eq!(reply.id(), 9);
+        assert_eq!(reply.answers.len(), 1);
+    }
+
+    #[test]
+    fn it_verifies_the_echoed_case_of_randomized_names() {
+        let swapped = AtomicBool::new(false);
+        let addr = upstream(move |query| {
+            let q = query.questions[0].clone();
+            assert_eq!(q.name(), &"example.com".parse::<DomainName>().unwrap());
+
+            // an upstream that does not preserve the case is not trusted, so the query is
+            // asked again once it times out
+            if !swapped.swap(true, Ordering::SeqCst) {
+                let name: String = q
+                    .name()
+                    .to_string()
+                    .chars()
+                    .map(|c| match c.is_ascii_lowercase() {
+                        true => c.to_ascii_uppercase(),
+                        false => c.to_ascii_lowercase(),
+                    })
+                    .collect();
+                let altered = Message::query(query.id(), &q.set_name(name.parse().unwrap()));
+                return Some(Message::reply(altered));
+            }
+
+            Some(Message::reply(query).set_answer(a_record(&q)))
+        });
+        let resolver = Resolver::new(vec![addr]).case_randomization(true);
+
+        // the answer and its cached copy keep the case of the client's question
+        let query = b"\x00\x09\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00\x00\x01\x00\x01";
+        for _ in 0..2 {
+            let reply = resolver.resolve(query).unwrap().unwrap();
+            assert_eq!(reply.answers.len(), 1);
+            assert_eq!(reply.answers[0].name().labels(), [b"example", &b"com"[..]]);
+        }
+        assert_eq!(resolver.cache_stats().hits, 1);
+    }
+
+    fn released_port() -> SocketAddr {
+        UdpSocket::bind("127.0.0.1:0")
+            .and_then(|socket| socket.local_addr())
+            .unwrap()
+    }
+
+    #[test]
+    fn it_fails_over_and_skips_dead_upstreams() {
+        let alive = upstream(|query| {
+            let q = query.questions[0].clone();
+            Some(Message::reply(query).set_answer(a_record(&q)))
+        });
+        let resolver = Resolver::new(vec![released_port(), alive]).cache(Cache::new(0, 0, 0, 0));
+
+        let query = b"\x00\x09\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00\x00\x01\x00\x01";
+        for _ in 0..3 {
+            assert_eq!(resolver.resolve(query).unwrap().unwrap().answers.len(), 1);
+        }
+        assert!(!resolver.upstreams[0].is_healthy());
+        assert!(resolver.upstreams[1].is_healthy());
+    }
+
+    #[test]
+    fn it_answers_servfail_when_every_upstream_fails() {
+        let resolver = Resolver::new(vec![released_port(), released_port()]);
+
+        let query = b"\x00\x09\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00\x00\x01\x00\x01";
+        let reply = resolver.resolve(query).unwrap().unwrap();
+        assert_eq!(reply.id(), 9);
+        assert_eq!(reply.header.rcode(), Rcode::ServerErr);
+        assert_eq!(reply.questions.len(), 1);
+    }
+
+    #[test]
+    fn it_relays_negative_upstream_answers() {
+        let addr = upstream(once(|query| {
+            assert!(query.header.rd());
+
+            // example.com SOA ns.example.com host.example.com 1 2 3 4 5
+            let soa: Vec<u8> = [
+                &b"\x07example\x03com\x00\x00\x06\x00\x01\x00\x00\x00\x3c\x00\x36"[..],
+                b"\x02ns\x07example\x03com\x00\x04host\x07example\x03com\x00",
+                b"\x00\x00\x00\x01\x00\x00\x00\x02\x00\x00\x00\x03\x00\x00\x00\x04\x00\x00\x00\x05",
+            ]
+            .concat();
+            let mut reply = Message::reply(query)
+                .set_rcode(Rcode::NonexistentDomain)
+                .set_authority(Answer::new(&mut std::io::Cursor::new(soa.as_slice())).unwrap());
+            reply.header = reply.header.set_aa(true).set_ra(true);
+            reply
+        }));
+        let resolver = Resolver::new(vec![addr]);
+
+        let query = b"\x00\x09\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x03www\x07example\x03com\x00\x00\x01\x00\x01";
+        let reply = resolver.resolve(query).unwrap().unwrap();
+        assert_eq!(reply.id(), 9);
+        assert_eq!(reply.header.rcode(), Rcode::NonexistentDomain);
+        assert!(reply.header.aa() && reply.header.ra() && !reply.header.ad());
+        assert!(reply.answers.is_empty());
+        assert_eq!(reply.authorities.len(), 1);
+        assert_eq!(reply.authorities[0].r#type(), RecordType::Soa);
+
+        // the upstream is gone, but the NXDOMAIN is cached for any type
+        let query = b"\x00\x0a\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x03www\x07example\x03com\x00\x00\x1c\x00\x01";
+        let reply = resolver.resolve(query).unwrap().unwrap();
+        assert_eq!(reply.header.rcode(), Rcode::NonexistentDomain);
+        assert_eq!(reply.authorities.len(), 1);
+    }
+
+    #[test]
+    fn it_serves_cached_answers_without_the_upstream() {
+        let addr = upstream(once(|query| {
+            let q = query.questions[0].clone();
+            Message::reply(query).set_answer(a_record(&q))
+        }));
+        let resolver = Resolver::new(vec![addr]);
+
+        let query = b"\x00\x09\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00\x00\x01\x00\x01";
+        assert_eq!(resolver.resolve(query).unwrap().unwrap().answers.len(), 1);
+
+        let reply = resolver.resolve(query).unwrap().unwrap();
+        assert_eq!(reply.header.rcode(), Rcode::NoErr);
+        assert_eq!(reply.answers.len(), 1);
+        assert!(reply.answers[0].ttl() <= 60);
+        assert_eq!(resolver.cache_stats().hits, 1);
+        assert_eq!(resolver.cache_stats().misses, 1);
+    }
+
+    #[test]
+    fn it_does_not_cache_records_unrelated_to_the_question() {
+        // slips an address for another name into the answer
+        let addr = upstream(|query| {
+            let q = query.questions[0].clone();
+            let victim = q.clone().set_name("victim.test".parse().unwrap());
+            let reply = Message::reply(query)
+                .set_answer(a_record(&q))
+                .set_answer(a_record(&victim));
+            Some(reply)
+        });
+        let resolver = Resolver::new(vec![addr]);
+
+        let query = b"\x00\x09\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00\x00\x01\x00\x01";
+        assert_eq!(resolver.resolve(query).unwrap().unwrap().answers.len(), 2);
+
+        let victim = Question::from_parts(
+            "victim.test".parse().unwrap(),
+            RecordType::A,
+            RecordClass::In,
+        );
+        assert!(resolver.cache.lookup(&victim).is_none());
+        assert_eq!(resolver.cache_stats().hits, 0);
+    }
+
+    fn truncated(query: Message) -> Option<Message> {
+        let q = query.questions[0].clone();
+        let mut reply = Message::reply(query).set_answer(a_record(&q));
+        reply.header = reply.header.set_tc(true);
+        Some(reply)
+    }
+
+    #[test]
+    fn it_retries_truncated_replies_over_tcp() {
+        let addr = upstream(truncated);
+        let listener = TcpListener::bind(addr).unwrap();
+        let resolver = Resolver::new(vec![addr]);
+
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let len = u16::from_be_bytes(utils::read_2_bytes(&mut stream).unwrap());
+            let query = utils::read_n_bytes(&mut stream, len as usize).unwrap();
+            let query = Message::try_from(query.as_slice()).unwrap();
+            let q = query.questions[0].clone();
+            let reply = Message::reply(query)
+                .set_answer(a_record(&q))
+                .set_answer(a_record(&q).set_ttl(30))
+                .as_bytes();
+            let len = (reply.len() as u16).to_be_bytes();
+            stream.write_all(&[&len[..], &reply].concat()).unwrap();
+        });
+
+        let query = b"\x00\x09\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00\x00\x01\x00\x01";
+        let reply = resolver.resolve(query).unwrap().unwrap();
+        assert!(!reply.header.tc());
+        assert_eq!(reply.answers.len(), 2);
+        assert_eq!(resolver.cache_stats().entries, 1);
+    }
+
+    #[test]
+    fn it_relays_truncated_replies_without_caching_them() {
+        // nothing listens on TCP
+        let resolver = Resolver::new(vec![upstream(truncated)]);
+
+        let query = b"\x00\x09\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00\x00\x01\x00\x01";
+        let reply = resolver.resolve(query).unwrap().unwrap();
+        assert!(reply.header.tc());
+        assert_eq!(reply.answers.len(), 1);
+        assert_eq!(resolver.cache_stats().entries, 0);
+    }
+
+    #[test]
+    fn it_serves_stale_answers_while_refreshing() {
+        // the first answer expires after a second, the refreshed one lasts
+        let answered = AtomicBool::new(false);
+        let addr = upstream(move |query| {
+            let q = query.questions[0].clone();
+            let ttl = match answered.swap(true, Ordering::SeqCst) {
+                false => 1,
+                true => 60,
+            };
+            Some(Message::reply(query).set_answer(a_record(&q).set_ttl(ttl)))
+        });
+        let resolver = Resolver::new(vec![addr]);
+
+        let query = b"\x00\x09\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00\x00\x01\x00\x01";
+        assert_eq!(
+            resolver.resolve(query).unwrap().unwrap().answers[0].ttl(),
+            1
+        );
+        thread::sleep(Duration::from_millis(1100));
+
+        let reply = resolver.resolve(query).unwrap().unwrap();
+        assert_eq!(reply.header.rcode(), Rcode::NoErr);
+        assert_eq!(reply.answers[0].ttl(), 30);
+        assert_eq!(resolver.cache_stats().stale_hits, 1);
+
+        // a second lookup does not queue the same refresh again
+        resolver.resolve(query).unwrap().unwrap();
+        let queue = resolver.refresh_queue.lock().unwrap();
+        let q = queue.try_recv().unwrap();
+        assert!(queue.try_recv().is_err());
+        drop(queue);
+
+        resolver.refresh(&q);
+        assert!(resolver.refreshing.lock().unwrap().is_empty());
+        let reply = resolver.resolve(query).unwrap().unwrap();
+        assert!(reply.answers[0].ttl() > 30);
+    }
+
+    #[test]
+    fn it_forwards_by_domain_suffix() {
+        // each upstream answers with its own address
+        let answering = |last: u8| {
+            upstream(move |query| {
+                let q = query.questions[0].clone();
+                let data = Rdata::A([10, 0, 0, last]);
+                let answer = Answer::record(q.name().clone(), q.r#type(), q.class(), 60, data);
+                Some(Message::reply(query).set_answer(answer))
+            })
+        };
+        let (public, internal) = (answering(1), answering(2));
+        let rules = vec![
+            format!("example={internal}").parse().unwrap(),
+            "blocked.example=/refuse".parse().unwrap(),
+        ];
+        let resolver = Resolver::new(vec![public]).forward_rules(rules);
+        let query = |name: &str| lookup(&resolver, name, RecordType::A, RecordClass::In);
+
+        assert!(matches!(
+            query("www.example").answers[0].data(),
+            Rdata::A([10, 0, 0, 2])
+        ));
+        assert!(matches!(
+            query("example.com").answers[0].data(),
+            Rdata::A([10, 0, 0, 1])
+        ));
+
+        let reply = query("ads.blocked.example");
+        assert_eq!(reply.header.rcode(), Rcode::Refused);
+        assert!(reply.answers.is_empty());
+    }
+
+    #[test]
+    fn it_answers_authoritatively_from_zones() {
+        let zone = zone::tests::zone(
+            "resolver",
+            "example.com",
+            "$TTL 60\n@ SOA ns1 hostmaster 1 2 3 4 5\nwww A 192.0.2.1\nsub NS ns.sub\nns.sub A 192.0.2.2\n",
+        )
+        .unwrap();
+        // no upstream answers, so anything not from the zone would fail
+        let resolver = Resolver::new(vec![released_port()]).zones(vec![zone]);
+
+        let query = |name: &str| lookup(&resolver, name, RecordType::A, RecordClass::In);
+
+        let reply = query("www.example.com");
+        assert_eq!(reply.header.rcode(), Rcode::NoErr);
+        assert!(reply.header.aa());
+        assert!(matches!(reply.answers[0].data(), Rdata::A([192, 0, 2, 1])));
+
+        let reply = query("ftp.example.com");
+        assert_eq!(reply.header.rcode(), Rcode::NonexistentDomain);
+        assert!(reply.header.aa());
+        assert_eq!(reply.authorities[0].r#type(), RecordType::Soa);
+
+        let reply = query("www.sub.example.com");
+        assert_eq!(reply.header.rcode(), Rcode::NoErr);
+        assert!(!reply.header.aa());
+        assert_eq!(reply.authorities[0].r#type(), RecordType::Ns);
+        assert_eq!(reply.additionals[0].r#type(), RecordType::A);
+        assert_eq!(resolver.cache_stats().misses, 0);
+    }
+
+    #[test]
+    fn it_prefers_chaos_zones_to_the_identity() {
+        let zone = zone::tests::zone(
+            "resolver-ch",
+            "bind",
+            "$TTL 0\n@ CH SOA ns hostmaster 1 2 3 4 5\nversion CH TXT \"from-zone\"\n",
+        )
+        .unwrap();
+        let identity = Identity::new(Some("cds-0.1".into()), Some("ns1".into()));
+        let resolver = Resolver::new(vec![]).identity(identity).zones(vec![zone]);
+
+        let query = |name: &str| lookup(&resolver, name, RecordType::Txt, RecordClass::Ch);
+
+        let reply = query("version.bind");
+        assert!(reply.header.aa());
+        assert!(matches!(reply.answers[0].data(), Rdata::Txt(txt) if txt[0] == b"from-zone"));
+
+        // names outside the zone still get the identity
+        let reply = query("id.server");
+        assert!(matches!(reply.answers[0].data(), Rdata::Txt(txt) if txt[0] == b"ns1"));
+    }
+}
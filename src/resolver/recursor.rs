@@ -0,0 +1,441 @@
+use super::{
+    recursive_reply, restore_case, Answer, DomainName, Edns, Message, Question, Rcode, Rdata,
+    RecordClass, RecordType, Resolver, Result, MAX_CNAMES, MAX_UDP_PAYLOAD_SIZE,
+};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+// Ref: https://www.iana.org/domains/root/servers
+const ROOT_SERVERS: [Ipv4Addr; 13] = [
+    Ipv4Addr::new(198, 41, 0, 4),
+    Ipv4Addr::new(170, 247, 170, 2),
+    Ipv4Addr::new(192, 33, 4, 12),
+    Ipv4Addr::new(199, 7, 91, 13),
+    Ipv4Addr::new(192, 203, 230, 10),
+    Ipv4Addr::new(192, 5, 5, 241),
+    Ipv4Addr::new(192, 112, 36, 4),
+    Ipv4Addr::new(198, 97, 190, 53),
+    Ipv4Addr::new(192, 36, 148, 17),
+    Ipv4Addr::new(192, 58, 128, 30),
+    Ipv4Addr::new(193, 0, 14, 129),
+    Ipv4Addr::new(199, 7, 83, 42),
+    Ipv4Addr::new(202, 12, 27, 33),
+];
+pub const DNS_PORT: u16 = 53;
+
+const QUERY_TIMEOUT: Duration = Duration::from_millis(1500);
+// Limits on the work a single question may cause, counting the lookups of nameserver
+// addresses it needs along the way.
+const MAX_QUERIES: usize = 64;
+const MAX_DEPTH: usize = 4;
+
+pub fn root_hints() -> Vec<SocketAddr> {
+    ROOT_SERVERS
+        .iter()
+        .map(|&ip| SocketAddr::new(ip.into(), DNS_PORT))
+        .collect()
+}
+
+// Final outcome for one name and type, after following any CNAME chain.
+struct Resolution {
+    rcode: Rcode,
+    answers: Vec<Answer>,
+    authorities: Vec<Answer>,
+}
+
+// Iterative resolution starting from the root hints.
+// Ref: https://datatracker.ietf.org/doc/html/rfc1034#section-5.3.3
+impl Resolver {
+    pub(super) fn recurse(&self, q: &Question) -> Result<Message> {
+        let mut budget = MAX_QUERIES;
+        let resolution = self.resolve_name(q.name(), q.r#type(), 0, &mut budget)?;
+
+        Ok(recursive_reply(
+            q,
+            resolution.rcode,
+            resolution.answers,
+            resolution.authorities,
+        ))
+    }
+
+    fn resolve_name(
+        &self,
+        name: &DomainName,
+        r#type: RecordType,
+        depth: usize,
+        budget: &mut usize,
+    ) -> Result<Resolution> {
+        let mut name = name.clone();
+        let mut answers: Vec<Answer> = vec![];
+        let mut cnames = 0;
+
+        loop {
+            let reply = self.iterate(&name, r#type, depth, budget)?;
+            let mut records = reply.answers;
+            let queried = name.clone();
+
+            // An answer may already carry part of the chain, typically when the target
+            // lives in the same zone.
+            loop {
+                let is_answer = |a: &Answer| a.name() == &name && a.r#type() == r#type;
+
+                if records.iter().any(is_answer) {
+                    answers.extend(records.into_iter().filter(is_answer));
+                    return Ok(Resolution {
+                        rcode: Rcode::NoErr,
+                        answers,
+                        authorities: vec![],
+                    });
+                }
+
+                let Some(i) = records
+                    .iter()
+                    .position(|a| a.name() == &name && a.r#type() == RecordType::Cname)
+                else {
+                    break;
+                };
+
+                cnames += 1;
+                if cnames > MAX_CNAMES {
+                    return Err(err!("CNAME chain of {queried} is too long"));
+                }
+
+                let cname = records.remove(i);
+                if let Rdata::Cname(target) = cname.data() {
+                    name = target.clone();
+                }
+                answers.push(cname);
+            }
+
+            // The chain leads to a name this reply says nothing about, so ask again.
+            if name != queried && reply.header.rcode() == Rcode::NoErr {
+                continue;
+            }
+
+            return Ok(Resolution {
+                rcode: reply.header.rcode(),
+                answers,
+                authorities: reply
+                    .authorities
+                    .into_iter()
+                    .filter(|a| a.r#type() == RecordType::Soa)
+                    .collect(),
+            });
+        }
+    }
+
+    // Follows referrals down from the root until a server answers for the name itself.
+    fn iterate(
+        &self,
+        name: &DomainName,
+        r#type: RecordType,
+        depth: usize,
+        budget: &mut usize,
+    ) -> Result<Message> {
+        let mut zone = DomainName::root();
+        let mut servers = self.root_hints.clone();
+
+        loop {
+            let mut reply = self.ask(&servers, name, r#type, budget)?;
+
+            // A server is only trusted for records within the zone it was asked about.
+            reply.answers.retain(|a| a.name().is_subdomain_of(&zone));
+            reply
+                .authorities
+                .retain(|a| a.name().is_subdomain_of(&zone));
+
+            let is_referral = !reply.header.aa()
+                && reply.answers.is_empty()
+                && reply.header.rcode() == Rcode::NoErr;
+            let nameservers: Vec<&Answer> = reply
+                .authorities
+                .iter()
+                .filter(|a| a.r#type() == RecordType::Ns)
+                .collect();
+
+            if !is_referral || nameservers.is_empty() {
+                return Ok(reply);
+            }
+
+            // Every referral has to move strictly closer to the name, which also rules out
+            // loops between servers referring to each other.
+            let cut = nameservers[0].name().clone();
+            if cut.labels().len() <= zone.labels().len()
+                || !cut.is_subdomain_of(&zone)
+                || !name.is_subdomain_of(&cut)
+            {
+                return Err(err!("Bad referral to {cut} while resolving {name}"));
+            }
+
+            let targets: Vec<DomainName> = nameservers
+                .iter()
+                .filter(|a| a.name() == &cut)
+                .filter_map(|a| match a.data() {
+                    Rdata::Ns(target) => Some(target.clone()),
+                    _ => None,
+                })
+                .collect();
+
+            servers = self.nameserver_addrs(&reply, &cut, &targets, depth, budget)?;
+            zone = cut;
+        }
+    }
+
+    // Addresses from glue records when the referral carries them. Glue is only trusted for
+    // nameservers inside the delegated zone; any other nameserver is resolved separately.
+    fn nameserver_addrs(
+        &self,
+        referral: &Message,
+        cut: &DomainName,
+        targets: &[DomainName],
+        depth: usize,
+        budget: &mut usize,
+    ) -> Result<Vec<SocketAddr>> {
+        let glue: Vec<SocketAddr> = referral
+            .additionals
+            .iter()
+            .filter(|a| a.name().is_subdomain_of(cut) && targets.contains(a.name()))
+            .filter_map(|a| self.address(a))
+            .collect();
+
+        if !glue.is_empty() {
+            return Ok(glue);
+        }
+
+        if depth >= MAX_DEPTH {
+            return Err(err!("Too many nested lookups below {cut}"));
+        }
+
+        for target in targets {
+            for r#type in [RecordType::A, RecordType::Aaaa] {
+                let addrs: Vec<SocketAddr> =
+                    match self.resolve_name(target, r#type, depth + 1, budget) {
+                        Ok(resolution) => resolution
+                            .answers
+                            .iter()
+                            .filter_map(|a| self.address(a))
+                            .collect(),
+                        Err(err) => {
+                            eprintln!("Cannot resolve nameserver {target}: {err}");
+                            break;
+                        }
+                    };
+
+                if !addrs.is_empty() {
+                    return Ok(addrs);
+                }
+            }
+        }
+
+        Err(err!("No address for any nameserver of {cut}"))
+    }
+
+    fn address(&self, record: &Answer) -> Option<SocketAddr> {
+        let ip: IpAddr = match record.data() {
+            Rdata::A(octets) => Ipv4Addr::from(*octets).into(),
+            Rdata::Aaaa(octets) => Ipv6Addr::from(*octets).into(),
+            _ => return None,
+        };
+
+        Some(SocketAddr::new(ip, self.nameserver_port))
+    }
+
+    // Asks each server in turn until one gives a usable reply.
+    fn ask(
+        &self,
+        servers: &[SocketAddr],
+        name: &DomainName,
+        r#type: RecordType,
+        budget: &mut usize,
+    ) -> Result<Message> {
+        let q = Question::from_parts(name.clone(), r#type, RecordClass::In);
+
+        for &server in servers {
+            if *budget == 0 {
+                return Err(err!("Too many queries while resolving {name}"));
+            }
+            *budget -= 1;
+
+            let query = self
+                .outgoing_query(&q)?
+                .set_edns(Edns::new(MAX_UDP_PAYLOAD_SIZE));
+
+            match self.forward(server, &query, QUERY_TIMEOUT) {
+                Ok(reply) if reply.header.tc() => eprintln!("{server} sent a truncated reply"),
+                Ok(reply)
+                    if matches!(
+                        reply.header.rcode(),
+                        Rcode::NoErr | Rcode::NonexistentDomain
+                    ) =>
+                {
+                    return Ok(restore_case(reply, &q))
+                }
+                Ok(reply) => eprintln!("{server} answered {:?}", reply.header.rcode()),
+                Err(err) => eprintln!("{server} failed: {err}"),
+            }
+        }
+
+        Err(err!("No nameserver answered for {name}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::UdpSocket;
+    use std::thread;
+
+    fn rr(name: &str, r#type: RecordType, data: Rdata) -> Answer {
+        Answer::record(name.parse().unwrap(), r#type, RecordClass::In, 60, data)
+    }
+
+    fn ns(name: &str, target: &str) -> Answer {
+        rr(name, RecordType::Ns, Rdata::Ns(target.parse().unwrap()))
+    }
+
+    fn a(name: &str, ip: [u8; 4]) -> Answer {
+        rr(name, RecordType::A, Rdata::A(ip))
+    }
+
+    // A fake nameserver on loopback filling replies through `respond`.
+    fn nameserver(addr: SocketAddr, respond: fn(&str, Message) -> Message) {
+        let socket = UdpSocket::bind(addr).unwrap();
+
+        thread::spawn(move || {
+            let mut buf = [0u8; 512];
+
+            while let Ok((size, from)) = socket.recv_from(&mut buf) {
+                let query = Message::try_from(&buf[..size]).unwrap();
+                let name = query.questions[0].name().to_string().to_lowercase();
+                let reply = respond(&name, Message::reply(query));
+                socket.send_to(&reply.as_bytes(), from).unwrap();
+            }
+        });
+    }
+
+    fn authoritative(msg: Message) -> Message {
+        let mut msg = msg;
+        msg.header = msg.header.set_aa(true);
+        msg
+    }
+
+    // root refers to com., which refers example.com. to a nameserver under net.
+    fn hierarchy() -> SocketAddr {
+        let root = UdpSocket::bind("127.0.0.2:0").unwrap();
+        let root_addr = root.local_addr().unwrap();
+        let port = root_addr.port();
+        drop(root);
+
+        nameserver(root_addr, |name, reply| {
+            if name.ends_with("net.") {
+                reply
+                    .set_authority(ns("net", "ns.net"))
+                    .set_additional(a("ns.net", [127, 0, 0, 5]))
+            } else {
+                reply
+                    .set_authority(ns("com", "ns.com"))
+                    .set_additional(a("ns.com", [127, 0, 0, 3]))
+            }
+        });
+        nameserver(([127, 0, 0, 3], port).into(), |_, reply| {
+            reply.set_authority(ns("example.com", "ns1.example.net"))
+        });
+        nameserver(([127, 0, 0, 5], port).into(), |name, reply| match name {
+            "ns1.example.net." => authoritative(reply.set_answer(a(name, [127, 0, 0, 4]))),
+            _ => authoritative(reply.set_rcode(Rcode::NonexistentDomain)),
+        });
+        nameserver(([127, 0, 0, 4], port).into(), |name, reply| {
+            let soa = Rdata::Soa {
+                mname: "ns1.example.net".parse().unwrap(),
+                rname: "host.example.com".parse().unwrap(),
+                serial: 1,
+                refresh: 2,
+                retry: 3,
+                expire: 4,
+                minimum: 5,
+            };
+
+            match name {
+                "www.example.com." => authoritative(
+                    reply
+                        .set_answer(rr(
+                            name,
+                            RecordType::Cname,
+                            Rdata::Cname("web.example.com".parse().unwrap()),
+                        ))
+                        .set_answer(a("web.example.com", [10, 0, 0, 1])),
+                ),
+                _ => authoritative(reply.set_rcode(Rcode::NonexistentDomain).set_authority(rr(
+                    "example.com",
+                    RecordType::Soa,
+                    soa,
+                ))),
+            }
+        });
+
+        root_addr
+    }
+
+    fn question(name: &str) -> Question {
+        Question::from_parts(name.parse().unwrap(), RecordType::A, RecordClass::In)
+    }
+
+    // The fake nameservers all listen on the port of the root.
+    fn recursor(root: SocketAddr) -> Resolver {
+        let mut resolver = Resolver::new(vec![]).root_hints(vec![root]);
+        resolver.nameserver_port = root.port();
+        resolver
+    }
+
+    #[test]
+    fn it_contacts_nameservers_on_the_standard_port() {
+        let resolver = Resolver::new(vec![]).root_hints(vec!["127.0.0.2:5353".parse().unwrap()]);
+
+        let addr = resolver.address(&a("ns.example", [192, 0, 2, 1]));
+        assert_eq!(addr, Some("192.0.2.1:53".parse().unwrap()));
+
+        let aaaa = Rdata::Aaaa(Ipv6Addr::LOCALHOST.octets());
+        let addr = resolver.address(&rr("ns.example", RecordType::Aaaa, aaaa));
+        assert_eq!(addr, Some("[::1]:53".parse().unwrap()));
+    }
+
+    #[test]
+    fn it_follows_referrals_and_cnames() {
+        let resolver = recursor(hierarchy());
+
+        let reply = resolver.recurse(&question("www.example.com")).unwrap();
+        assert_eq!(reply.header.rcode(), Rcode::NoErr);
+        assert_eq!(reply.answers.len(), 2);
+        assert_eq!(
+            reply.answers[1].to_string(),
+            "web.example.com. 60 IN A 10.0.0.1"
+        );
+
+        let reply = resolver.recurse(&question("missing.example.com")).unwrap();
+        assert_eq!(reply.header.rcode(), Rcode::NonexistentDomain);
+        assert!(reply.answers.is_empty());
+        assert_eq!(reply.authorities[0].r#type(), RecordType::Soa);
+    }
+
+    #[test]
+    fn it_gives_up_on_unresolvable_delegations() {
+        let root = UdpSocket::bind("127.0.0.2:0").unwrap();
+        let root_addr = root.local_addr().unwrap();
+        drop(root);
+
+        // the nameserver of loop. can only be found through loop. itself
+        nameserver(root_addr, |_, reply| {
+            reply.set_authority(ns("loop", "ns.loop"))
+        });
+
+        let resolver = recursor(root_addr);
+        assert!(resolver.recurse(&question("www.loop")).is_err());
+
+        // a server referring back up the tree
+        nameserver(([127, 0, 0, 6], root_addr.port()).into(), |_, reply| {
+            reply.set_authority(ns("com", "ns.com"))
+        });
+        let resolver = recursor(([127, 0, 0, 6], root_addr.port()).into());
+        assert!(resolver.recurse(&question("www.loop")).is_err());
+    }
+}
@@ -0,0 +1,72 @@
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Consecutive failures after which an upstream is considered dead.
+const MAX_FAILURES: u32 = 3;
+// How long a dead upstream is skipped before it gets another chance.
+const DOWN_FOR: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub struct Upstream {
+    addr: SocketAddr,
+    health: Mutex<Health>,
+}
+
+#[derive(Debug, Default)]
+struct Health {
+    failures: u32,
+    down_until: Option<Instant>,
+}
+
+impl Upstream {
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            health: Mutex::new(Health::default()),
+        }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.health
+            .lock()
+            .map(|health| {
+                health
+                    .down_until
+                    .map_or(true, |until| until <= Instant::now())
+            })
+            .unwrap_or(true)
+    }
+
+    pub fn succeeded(&self) {
+        if let Ok(mut health) = self.health.lock() {
+            *health = Health::default();
+        }
+    }
+
+    pub fn failed(&self) {
+        if let Ok(mut health) = self.health.lock() {
+            health.failures += 1;
+
+            if health.failures >= MAX_FAILURES {
+                health.down_until = Some(Instant::now() + DOWN_FOR);
+            }
+        }
+    }
+}
+
+// Healthy upstreams in their configured order. When all of them look dead every one is
+// tried anyway, since failing outright cannot be better than a late answer.
+pub fn candidates(upstreams: &[Upstream]) -> Vec<&Upstream> {
+    let healthy: Vec<&Upstream> = upstreams.iter().filter(|u| u.is_healthy()).collect();
+
+    if healthy.is_empty() {
+        upstreams.iter().collect()
+    } else {
+        healthy
+    }
+}
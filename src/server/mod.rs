@@ -0,0 +1,213 @@
+use crate::{
+    resolver::{self, Cache, ForwardRule, Identity, Resolver},
+    zone::Zone,
+    Result,
+};
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs, UdpSocket};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+mod tcp;
+mod udp;
+
+const DEFAULT_WORKERS: usize = 16;
+
+pub struct Server {
+    addr: SocketAddr,
+    resolver: Option<Resolver>,
+    forward_rules: Vec<ForwardRule>,
+    zones: Vec<Zone>,
+    identity: Identity,
+    compression: bool,
+    workers: usize,
+    case_randomization: bool,
+    root_hints: Vec<SocketAddr>,
+    cache: Cache,
+    max_stale: Option<Duration>,
+    stats_interval: Option<Duration>,
+}
+
+impl Server {
+    fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            resolver: None,
+            forward_rules: vec![],
+            zones: vec![],
+            identity: Identity::default(),
+            compression: true,
+            workers: DEFAULT_WORKERS,
+            case_randomization: false,
+            root_hints: vec![],
+            cache: Cache::default(),
+            max_stale: None,
+            stats_interval: None,
+        }
+    }
+
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<Self> {
+        addr.to_socket_addrs()?
+            .next()
+            .ok_or(err!("SocketAddr is None"))
+            .map(Self::new)
+    }
+
+    // Upstreams are tried in the given order. Without any, names are resolved recursively.
+    pub fn resolver<A: ToSocketAddrs>(self, addrs: Vec<A>) -> Result<Self> {
+        let mut upstreams: Vec<SocketAddr> = vec![];
+
+        for addr in addrs {
+            upstreams.extend(addr.to_socket_addrs()?.next());
+        }
+
+        Ok(Self {
+            resolver: Some(Resolver::new(upstreams)),
+            ..self
+        })
+    }
+
+    // Names under a rule's suffix go to the rule's own upstreams, the longest suffix winning.
+    // Rules read `SUFFIX=ADDR[,ADDR...][/recurse|/refuse]`.
+    pub fn forward(mut self, rules: Vec<String>) -> Result<Self> {
+        for rule in rules {
+            self.forward_rules.push(rule.parse()?);
+        }
+
+        Ok(self)
+    }
+
+    // Adds the forwarding rules of a file with one rule per line.
+    pub fn forward_file<P: AsRef<Path>>(mut self, path: Option<P>) -> Result<Self> {
+        if let Some(path) = path {
+            self.forward_rules
+                .extend(resolver::read_forward_rules(path)?);
+        }
+
+        Ok(self)
+    }
+
+    // Serves zones authoritatively from master files, given as `[ORIGIN=]FILE`.
+    pub fn zones(mut self, zones: Vec<String>) -> Result<Self> {
+        for zone in zones {
+            let zone = match zone.split_once('=') {
+                Some((origin, path)) => Zone::load(path, Some(origin.parse()?))?,
+                None => Zone::load(&zone, None)?,
+            };
+            self.zones.push(zone);
+        }
+
+        Ok(self)
+    }
+
+    pub fn identity(self, version: Option<String>, hostname: Option<String>) -> Self {
+        Self {
+            identity: Identity::new(version, hostname),
+            ..self
+        }
+    }
+
+    pub fn compression(self, compression: bool) -> Self {
+        Self {
+            compression,
+            ..self
+        }
+    }
+
+    // The number of UDP queries resolved at the same time.
+    pub fn workers(self, workers: usize) -> Self {
+        Self {
+            workers: workers.max(1),
+            ..self
+        }
+    }
+
+    pub fn case_randomization(self, case_randomization: bool) -> Self {
+        Self {
+            case_randomization,
+            ..self
+        }
+    }
+
+    // Replaces the built-in root servers that recursion starts from.
+    pub fn root_hints<A: ToSocketAddrs>(self, addrs: Vec<A>) -> Result<Self> {
+        let mut root_hints: Vec<SocketAddr> = vec![];
+
+        for addr in addrs {
+            root_hints.extend(addr.to_socket_addrs()?.next());
+        }
+
+        Ok(Self { root_hints, ..self })
+    }
+
+    // Capacities count RRsets and negative results respectively; 0 disables either.
+    pub fn cache(
+        self,
+        capacity: usize,
+        negative_capacity: usize,
+        min_ttl: u32,
+        max_ttl: u32,
+    ) -> Self {
+        Self {
+            cache: Cache::new(capacity, negative_capacity, min_ttl, max_ttl),
+            ..self
+        }
+    }
+
+    // How long expired cache entries are still served while they cannot be refreshed.
+    pub fn max_stale(self, max_stale: Duration) -> Self {
+        Self {
+            max_stale: Some(max_stale),
+            ..self
+        }
+    }
+
+    // Prints cache statistics to stderr this often.
+    pub fn stats_interval(self, stats_interval: Option<Duration>) -> Self {
+        Self {
+            stats_interval,
+            ..self
+        }
+    }
+
+    pub fn run(self) -> Result<()> {
+        let socket = UdpSocket::bind(self.addr)?;
+        let listener = TcpListener::bind(self.addr)?;
+        let cache = match self.max_stale {
+            Some(max_stale) => self.cache.max_stale(max_stale),
+            None => self.cache,
+        };
+        let resolver = self
+            .resolver
+            .ok_or(err!("Message resolver is not set"))?
+            .forward_rules(self.forward_rules)
+            .zones(self.zones)
+            .identity(self.identity)
+            .case_randomization(self.case_randomization)
+            .root_hints(self.root_hints)
+            .cache(cache);
+        let resolver = Arc::new(resolver);
+
+        {
+            let resolver = Arc::clone(&resolver);
+            thread::spawn(move || resolver.refresh_cache());
+        }
+
+        {
+            let resolver = Arc::clone(&resolver);
+            let compression = self.compression;
+            thread::spawn(move || tcp::listen(listener, resolver, compression));
+        }
+
+        if let Some(interval) = self.stats_interval {
+            let resolver = Arc::clone(&resolver);
+            thread::spawn(move || loop {
+                thread::sleep(interval);
+                eprintln!("{}", resolver.cache_stats());
+            });
+        }
+
+        udp::listen(socket, resolver, self.compression, self.workers)
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{resolver::Resolver, utils, Result};
+use std::io::{ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+// Ref: https://datatracker.ietf.org/doc/html/rfc7766#section-6.2
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_CONNECTIONS: usize = 128;
+const MAX_QUERIES_PER_CONNECTION: usize = 100;
+
+pub fn listen(listener: TcpListener, resolver: Arc<Resolver>, compression: bool) {
+    let connections = Arc::new(AtomicUsize::new(0));
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("Cannot accept TCP connection: {err}");
+                continue;
+            }
+        };
+
+        // Dropping the stream closes connections beyond the limit right away.
+        if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            connections.fetch_sub(1, Ordering::SeqCst);
+            continue;
+        }
+
+        let resolver = Arc::clone(&resolver);
+        let connections = Arc::clone(&connections);
+
+        thread::spawn(move || {
+            if let Err(err) = serve(stream, &resolver, compression) {
+                eprintln!("TCP connection failed: {err}");
+            }
+            connections.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+}
+
+// Queries are answered in the order they arrive, so a client may pipeline several of
+// them on one connection. The connection is closed once it stays idle for too long.
+fn serve(mut stream: TcpStream, resolver: &Resolver, compression: bool) -> Result<()> {
+    stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+    stream.set_write_timeout(Some(IDLE_TIMEOUT))?;
+
+    for _ in 0..MAX_QUERIES_PER_CONNECTION {
+        let query = match read_frame(&mut stream)? {
+            Some(query) => query,
+            None => break,
+        };
+
+        let Some(msg) = resolver.resolve(&query)? else {
+            continue;
+        };
+        let mut bytes = msg.encode(compression);
+
+        if bytes.len() > u16::MAX as usize {
+            bytes = msg
+                .truncate(u16::MAX as usize, compression)
+                .encode(compression);
+        }
+
+        write_frame(&mut stream, &bytes)?;
+    }
+
+    Ok(())
+}
+
+// Every message is prefixed with its length as a two byte field.
+fn read_frame(stream: &mut TcpStream) -> Result<Option<Vec<u8>>> {
+    let len = match utils::read_2_bytes(stream) {
+        Ok(bytes) => u16::from_be_bytes(bytes),
+        Err(crate::Error::Io(err))
+            if matches!(
+                err.kind(),
+                ErrorKind::UnexpectedEof | ErrorKind::WouldBlock | ErrorKind::TimedOut
+            ) =>
+        {
+            return Ok(None);
+        }
+        Err(err) => return Err(err),
+    };
+
+    utils::read_n_bytes(stream, len as usize).map(Some)
+}
+
+fn write_frame(stream: &mut TcpStream, bytes: &[u8]) -> Result<()> {
+    let len = (bytes.len() as u16).to_be_bytes();
+    stream.write_all(&[&len[..], bytes].concat())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{resolver::Identity, Message};
+
+    #[test]
+    fn it_answers_pipelined_queries() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let identity = Identity::new(Some("cds-0.1".into()), None);
+        let resolver = Arc::new(Resolver::new(vec![]).identity(identity));
+        thread::spawn(move || listen(listener, resolver, true));
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        // CHAOS TXT version.bind, answered without any upstream
+        let question =
+            b"\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07version\x04bind\x00\x00\x10\x00\x03";
+        stream
+            .write_all(
+                &[
+                    &b"\x00\x1e\x00\x01"[..],
+                    question,
+                    b"\x00\x1e\x00\x02",
+                    question,
+                ]
+                .concat(),
+            )
+            .unwrap();
+
+        for id in [1, 2] {
+            let reply = read_frame(&mut stream).unwrap().unwrap();
+            let msg = Message::try_from(reply.as_slice()).unwrap();
+            assert_eq!(msg.id(), id);
+            assert_eq!(msg.answers.len(), 1);
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use crate::{resolver::Resolver, Result, MAX_UDP_PAYLOAD_SIZE};
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// Large enough for any query within the payload size we advertise through EDNS.
+const BUF_SIZE: usize = MAX_UDP_PAYLOAD_SIZE as usize;
+// Queries waiting for a free worker. Once the queue is full the listener stops reading
+// and further datagrams pile up in the socket buffer, where the kernel drops the excess.
+const QUEUE_SIZE_PER_WORKER: usize = 8;
+
+type Job = (Vec<u8>, SocketAddr);
+
+pub fn listen(
+    socket: UdpSocket,
+    resolver: Arc<Resolver>,
+    compression: bool,
+    workers: usize,
+) -> Result<()> {
+    let (sender, receiver) = mpsc::sync_channel::<Job>(workers * QUEUE_SIZE_PER_WORKER);
+    let receiver = Arc::new(Mutex::new(receiver));
+
+    for _ in 0..workers {
+        let socket = socket.try_clone()?;
+        let resolver = Arc::clone(&resolver);
+        let receiver = Arc::clone(&receiver);
+        thread::spawn(move || work(socket, &resolver, &receiver, compression));
+    }
+
+    receive(&socket, sender)
+}
+
+fn receive(socket: &UdpSocket, sender: SyncSender<Job>) -> Result<()> {
+    let mut buf = [0u8; BUF_SIZE];
+
+    loop {
+        let (size, addr) = socket.recv_from(&mut buf)?;
+
+        if sender.send((buf[..size].to_vec(), addr)).is_err() {
+            return Err(err!("All UDP workers have stopped"));
+        }
+    }
+}
+
+fn work(
+    socket: UdpSocket,
+    resolver: &Resolver,
+    receiver: &Mutex<Receiver<Job>>,
+    compression: bool,
+) {
+    loop {
+        let job = match receiver.lock() {
+            Ok(receiver) => receiver.recv(),
+            Err(_) => return,
+        };
+
+        let (query, addr) = match job {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+
+        if let Err(err) = answer(&socket, resolver, &query, addr, compression) {
+            eprintln!("Cannot answer {addr}: {err}");
+        }
+    }
+}
+
+fn answer(
+    socket: &UdpSocket,
+    resolver: &Resolver,
+    query: &[u8],
+    addr: SocketAddr,
+    compression: bool,
+) -> Result<()> {
+    let Some(msg) = resolver.resolve(query)? else {
+        return Ok(());
+    };
+    let limit = msg.udp_payload_size();
+    let mut bytes = msg.encode(compression);
+
+    if bytes.len() > limit {
+        bytes = msg.truncate(limit, compression).encode(compression);
+    }
+
+    socket.send_to(&bytes, addr)?;
+    Ok(())
+}
@@ -19,8 +19,25 @@ pub fn read_4_bytes<R: Read>(r: &mut R) -> Result<[u8; 4]> {
     Ok(buf)
 }
 
+pub fn read_16_bytes<R: Read>(r: &mut R) -> Result<[u8; 16]> {
+    let mut buf = [0u8; 16];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
 pub fn read_n_bytes<R: Read>(r: &mut R, n: usize) -> Result<Vec<u8>> {
     let mut buf = vec![0u8; n];
     r.read_exact(&mut buf)?;
     Ok(buf)
 }
+
+pub fn random_bytes(n: usize) -> Result<Vec<u8>> {
+    let mut buf = vec![0u8; n];
+    getrandom::getrandom(&mut buf).map_err(|err| err!("Cannot get random bytes: {err}"))?;
+    Ok(buf)
+}
+
+pub fn random_u16() -> Result<u16> {
+    let bytes = random_bytes(2)?;
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
@@ -0,0 +1,440 @@
+use super::{
+    Answer, DomainName, Question, Rcode, Rdata, RecordClass, RecordType, Result, MAX_CNAMES,
+};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+mod parser;
+
+// Authoritative data for the names at and below `origin`, the owner of the SOA record.
+#[derive(Debug)]
+pub struct Zone {
+    origin: DomainName,
+    class: RecordClass,
+    soa: Answer,
+    records: HashMap<DomainName, Vec<Answer>>,
+    // Every owner along with the names between it and the origin, which exist as empty
+    // non-terminals when they own no records themselves.
+    names: HashSet<DomainName>,
+}
+
+// The zone's answer to a question, in the shape of a reply. Referrals to a delegated
+// subzone are not authoritative.
+#[derive(Debug)]
+pub struct Lookup {
+    pub rcode: Rcode,
+    pub authoritative: bool,
+    pub answers: Vec<Answer>,
+    pub authorities: Vec<Answer>,
+    pub additionals: Vec<Answer>,
+}
+
+impl Zone {
+    // Reads a master file. Relative names in it are completed with `origin`, or with the
+    // root when it is not given, until the file sets another one through `$ORIGIN`.
+    pub fn load<P: AsRef<Path>>(path: P, origin: Option<DomainName>) -> Result<Self> {
+        let path = path.as_ref();
+        let records = parser::parse_file(path, origin.unwrap_or_else(DomainName::root))?;
+        Self::new(records).map_err(|e| err!("{}: {e}", path.display()))
+    }
+
+    fn new(records: Vec<Answer>) -> Result<Self> {
+        let mut soas = records.iter().filter(|r| r.r#type() == RecordType::Soa);
+        let soa = soas.next().cloned().ok_or(err!("Zone has no SOA record"))?;
+        if soas.next().is_some() {
+            return Err(err!("Zone has more than one SOA record"));
+        }
+
+        let origin = soa.name().clone();
+        let class = soa.class();
+        let mut by_name: HashMap<DomainName, Vec<Answer>> = HashMap::new();
+        let mut names: HashSet<DomainName> = HashSet::new();
+
+        for record in records {
+            if !record.name().is_subdomain_of(&origin) {
+                return Err(err!("{} is outside the zone {origin}", record.name()));
+            }
+            if record.class() != class {
+                return Err(err!("{} is not in class {class}", record.name()));
+            }
+
+            let mut name = Some(record.name().clone());
+            while let Some(n) = name.filter(|n| n.is_subdomain_of(&origin)) {
+                name = n.parent();
+                names.insert(n);
+            }
+            by_name
+                .entry(record.name().clone())
+                .or_default()
+                .push(record);
+        }
+
+        // Ref: https://datatracker.ietf.org/doc/html/rfc1034#section-3.6.2
+        for (name, records) in by_name.iter() {
+            if records.iter().any(|r| r.r#type() == RecordType::Cname) && records.len() > 1 {
+                return Err(err!("CNAME at {name} is not the only record there"));
+            }
+        }
+
+        Ok(Self {
+            origin,
+            class,
+            soa,
+            records: by_name,
+            names,
+        })
+    }
+
+    // Ref: https://datatracker.ietf.org/doc/html/rfc1034#section-4.3.2
+    //      https://datatracker.ietf.org/doc/html/rfc4592#section-3.3.1 (wildcards)
+    pub fn lookup(&self, q: &Question) -> Lookup {
+        let mut lookup = Lookup {
+            rcode: Rcode::NoErr,
+            authoritative: true,
+            answers: vec![],
+            authorities: vec![],
+            additionals: vec![],
+        };
+        let mut name = q.name().clone();
+        let mut visited: HashSet<DomainName> = HashSet::new();
+
+        for _ in 0..=MAX_CNAMES {
+            // A chain leaving the zone ends here, for the requestor to follow, and so does
+            // one looping back to a name it already passed.
+            if !name.is_subdomain_of(&self.origin) || !visited.insert(name.clone()) {
+                return lookup;
+            }
+
+            if let Some(ns) = self.delegation(&name) {
+                lookup.authoritative = !lookup.answers.is_empty();
+                lookup.additionals = self.glue(&ns);
+                lookup.authorities = ns;
+                return lookup;
+            }
+
+            let records: Vec<Answer> = match self.records.get(&name) {
+                Some(records) => records.clone(),
+                None if self.names.contains(&name) => vec![],
+                None => match self.wildcard(&name) {
+                    // Synthesized records take the name that was asked for.
+                    Some(records) => records
+                        .iter()
+                        .map(|record| record.clone().set_name(name.clone()))
+                        .collect(),
+                    None => {
+                        lookup.rcode = Rcode::NonexistentDomain;
+                        lookup.authorities.push(self.negative_soa());
+                        return lookup;
+                    }
+                },
+            };
+
+            // ANY asks for every RRset at the name, CNAME included, without following it.
+            // Ref: https://datatracker.ietf.org/doc/html/rfc1034#section-4.3.2
+            let any = q.r#type() == RecordType::Any;
+            if q.r#type() != RecordType::Cname && !any {
+                if let Some(cname) = records.iter().find(|r| r.r#type() == RecordType::Cname) {
+                    if let Rdata::Cname(target) = cname.data() {
+                        name = target.clone();
+                    }
+                    lookup.answers.push(cname.clone());
+                    continue;
+                }
+            }
+
+            let answers: Vec<Answer> = records
+                .into_iter()
+                .filter(|record| any || record.r#type() == q.r#type())
+                .collect();
+
+            if answers.is_empty() {
+                lookup.authorities.push(self.negative_soa());
+            }
+            lookup.answers.extend(answers);
+            return lookup;
+        }
+
+        lookup
+    }
+
+    // The NS RRset of the highest zone cut between the origin and the name, the name
+    // itself included. Everything below a cut belongs to the subzone.
+    fn delegation(&self, name: &DomainName) -> Option<Vec<Answer>> {
+        let depth = name.labels().len() - self.origin.labels().len();
+
+        (0..depth).rev().find_map(|i| {
+            let cut = DomainName::from_labels(name.labels()[i..].to_vec()).ok()?;
+            let ns: Vec<Answer> = self
+                .records
+                .get(&cut)?
+                .iter()
+                .filter(|record| record.r#type() == RecordType::Ns)
+                .cloned()
+                .collect();
+
+            (!ns.is_empty()).then_some(ns)
+        })
+    }
+
+    // Addresses of the nameservers that only this zone can tell, as they live within it.
+    fn glue(&self, ns: &[Answer]) -> Vec<Answer> {
+        ns.iter()
+            .filter_map(|record| match record.data() {
+                Rdata::Ns(target) => self.records.get(target),
+                _ => None,
+            })
+            .flatten()
+            .filter(|record| matches!(record.r#type(), RecordType::A | RecordType::Aaaa))
+            .cloned()
+            .collect()
+    }
+
+    // Records of the wildcard at the closest encloser, the nearest existing ancestor.
+    fn wildcard(&self, name: &DomainName) -> Option<&Vec<Answer>> {
+        let mut encloser = name.parent()?;
+
+        while !self.names.contains(&encloser) {
+            encloser = encloser.parent()?;
+        }
+
+        self.records.get(&encloser.wildcard().ok()?)
+    }
+
+    // NXDOMAIN and NODATA come with the SOA, whose TTL there is at most its MINIMUM field.
+    // Ref: https://datatracker.ietf.org/doc/html/rfc2308#section-3
+    fn negative_soa(&self) -> Answer {
+        let ttl = match self.soa.data() {
+            Rdata::Soa { minimum, .. } => self.soa.ttl().min(*minimum),
+            _ => self.soa.ttl(),
+        };
+
+        self.soa.clone().set_ttl(ttl)
+    }
+}
+
+// The zone with the longest origin the question falls under.
+pub fn find<'a>(zones: &'a [Zone], q: &Question) -> Option<&'a Zone> {
+    zones
+        .iter()
+        .filter(|zone| zone.class == q.class() && q.name().is_subdomain_of(&zone.origin))
+        .max_by_key(|zone| zone.origin.labels().len())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    const ZONE: &str = "$TTL 3600
+@    SOA ns1 hostmaster 1 7200 1800 604800 300
+     NS  ns1
+ns1  A   192.0.2.1
+www  A   192.0.2.2
+     A   192.0.2.3
+";
+
+    // Loads the zone text through a temporary file named after `name`.
+    pub(crate) fn zone(name: &str, origin: &str, text: &str) -> Result<Zone> {
+        let path = env::temp_dir().join(format!("{name}-{}.zone", process::id()));
+        fs::write(&path, text).unwrap();
+        let zone = Zone::load(&path, Some(origin.parse().unwrap()));
+        fs::remove_file(&path).unwrap();
+        zone
+    }
+
+    fn question(name: &str, r#type: RecordType) -> Question {
+        Question::from_parts(name.parse().unwrap(), r#type, RecordClass::In)
+    }
+
+    #[test]
+    fn it_answers_from_its_records() {
+        let zone = zone("answers", "example.com", ZONE).unwrap();
+
+        let lookup = zone.lookup(&question("WWW.example.com", RecordType::A));
+        assert_eq!(lookup.rcode, Rcode::NoErr);
+        assert_eq!(lookup.answers.len(), 2);
+        assert!(lookup.authorities.is_empty());
+
+        // NODATA and NXDOMAIN carry the SOA with the negative TTL
+        let lookup = zone.lookup(&question("www.example.com", RecordType::Mx));
+        assert_eq!(lookup.rcode, Rcode::NoErr);
+        assert!(lookup.answers.is_empty());
+        assert_eq!(lookup.authorities[0].r#type(), RecordType::Soa);
+        assert_eq!(lookup.authorities[0].ttl(), 300);
+
+        let lookup = zone.lookup(&question("nope.example.com", RecordType::A));
+        assert_eq!(lookup.rcode, Rcode::NonexistentDomain);
+        assert_eq!(lookup.authorities[0].name().to_string(), "example.com.");
+    }
+
+    #[test]
+    fn it_answers_any_with_every_rrset() {
+        let zone = zone("any", "example.com", SEMANTICS).unwrap();
+
+        let lookup = zone.lookup(&question("example.com", RecordType::Any));
+        assert_eq!(lookup.rcode, Rcode::NoErr);
+        let types: Vec<RecordType> = lookup.answers.iter().map(Answer::r#type).collect();
+        assert_eq!(types, [RecordType::Soa, RecordType::Ns]);
+        assert!(lookup.authorities.is_empty());
+
+        // a CNAME is returned but not followed
+        let lookup = zone.lookup(&question("alias.example.com", RecordType::Any));
+        assert_eq!(lookup.answers.len(), 1);
+        assert_eq!(lookup.answers[0].r#type(), RecordType::Cname);
+    }
+
+    const SEMANTICS: &str = "$TTL 3600
+@       SOA   ns1 hostmaster 1 7200 1800 604800 300
+        NS    ns1
+ns1     A     192.0.2.1
+www     A     192.0.2.2
+alias   CNAME www
+chain   CNAME alias
+out     CNAME www.example.net.
+broken  CNAME missing
+loop1   CNAME loop2
+loop2   CNAME loop1
+*.wild  A     192.0.2.3
+*.cname CNAME www
+a.b.ent A     192.0.2.4
+sub     NS    ns.sub
+        NS    ns.example.net.
+ns.sub  A     192.0.2.5
+";
+
+    fn names(records: &[Answer]) -> Vec<String> {
+        records.iter().map(|r| r.name().to_string()).collect()
+    }
+
+    #[test]
+    fn it_follows_cname_chains_within_the_zone() {
+        let zone = zone("cnames", "example.com", SEMANTICS).unwrap();
+
+        let lookup = zone.lookup(&question("chain.example.com", RecordType::A));
+        assert_eq!(lookup.rcode, Rcode::NoErr);
+        assert!(lookup.authoritative);
+        assert_eq!(
+            names(&lookup.answers),
+            [
+                "chain.example.com.",
+                "alias.example.com.",
+                "www.example.com."
+            ]
+        );
+
+        // the CNAME itself when asked for
+        let lookup = zone.lookup(&question("chain.example.com", RecordType::Cname));
+        assert_eq!(names(&lookup.answers), ["chain.example.com."]);
+
+        let lookup = zone.lookup(&question("out.example.com", RecordType::A));
+        assert_eq!(lookup.rcode, Rcode::NoErr);
+        assert_eq!(lookup.answers.len(), 1);
+        assert!(lookup.authorities.is_empty());
+
+        // the rcode is about the last name of the chain
+        let lookup = zone.lookup(&question("broken.example.com", RecordType::A));
+        assert_eq!(lookup.rcode, Rcode::NonexistentDomain);
+        assert_eq!(lookup.answers.len(), 1);
+        assert_eq!(lookup.authorities[0].r#type(), RecordType::Soa);
+
+        // each CNAME of a loop shows up once
+        let lookup = zone.lookup(&question("loop1.example.com", RecordType::A));
+        let names: Vec<String> = lookup
+            .answers
+            .iter()
+            .map(|a| a.name().to_string())
+            .collect();
+        assert_eq!(names, ["loop1.example.com.", "loop2.example.com."]);
+    }
+
+    #[test]
+    fn it_synthesizes_answers_from_wildcards() {
+        let zone = zone("wildcards", "example.com", SEMANTICS).unwrap();
+
+        for name in ["host.wild.example.com", "a.b.wild.example.com"] {
+            let lookup = zone.lookup(&question(name, RecordType::A));
+            assert_eq!(lookup.rcode, Rcode::NoErr);
+            assert_eq!(names(&lookup.answers), [format!("{name}.")]);
+        }
+
+        let lookup = zone.lookup(&question("host.wild.example.com", RecordType::Mx));
+        assert_eq!(lookup.rcode, Rcode::NoErr);
+        assert!(lookup.answers.is_empty());
+        assert_eq!(lookup.authorities[0].r#type(), RecordType::Soa);
+
+        let lookup = zone.lookup(&question("x.cname.example.com", RecordType::A));
+        assert_eq!(
+            names(&lookup.answers),
+            ["x.cname.example.com.", "www.example.com."]
+        );
+
+        // no wildcard matches below a name that exists
+        let lookup = zone.lookup(&question("x.www.example.com", RecordType::A));
+        assert_eq!(lookup.rcode, Rcode::NonexistentDomain);
+    }
+
+    #[test]
+    fn it_answers_nodata_for_empty_non_terminals() {
+        let zone = zone("ents", "example.com", SEMANTICS).unwrap();
+
+        for name in ["b.ent.example.com", "ent.example.com", "wild.example.com"] {
+            let lookup = zone.lookup(&question(name, RecordType::A));
+            assert_eq!(lookup.rcode, Rcode::NoErr, "{name}");
+            assert!(lookup.answers.is_empty());
+            assert_eq!(lookup.authorities[0].r#type(), RecordType::Soa);
+        }
+
+        let lookup = zone.lookup(&question("c.b.ent.example.com", RecordType::A));
+        assert_eq!(lookup.rcode, Rcode::NonexistentDomain);
+    }
+
+    #[test]
+    fn it_refers_to_delegated_subzones() {
+        let zone = zone("delegations", "example.com", SEMANTICS).unwrap();
+
+        for (name, r#type) in [
+            ("host.sub.example.com", RecordType::A),
+            ("sub.example.com", RecordType::Ns),
+            ("ns.sub.example.com", RecordType::A),
+        ] {
+            let lookup = zone.lookup(&question(name, r#type));
+            assert_eq!(lookup.rcode, Rcode::NoErr);
+            assert!(!lookup.authoritative, "{name}");
+            assert!(lookup.answers.is_empty());
+            assert_eq!(lookup.authorities.len(), 2);
+            assert_eq!(lookup.authorities[0].r#type(), RecordType::Ns);
+            assert_eq!(names(&lookup.additionals), ["ns.sub.example.com."]);
+        }
+
+        // the apex NS is the zone's own, not a delegation
+        let lookup = zone.lookup(&question("example.com", RecordType::Ns));
+        assert!(lookup.authoritative);
+        assert_eq!(lookup.answers.len(), 1);
+    }
+
+    #[test]
+    fn it_finds_the_closest_zone() {
+        let parent = zone("parent", "example.com", ZONE).unwrap();
+        let child = zone("child", "sub.example.com", ZONE).unwrap();
+        let zones = [parent, child];
+
+        let origin =
+            |name: &str| find(&zones, &question(name, RecordType::A)).map(|z| z.origin.to_string());
+        assert_eq!(origin("a.sub.example.com").unwrap(), "sub.example.com.");
+        assert_eq!(origin("example.com").unwrap(), "example.com.");
+        assert!(origin("example.net").is_none());
+    }
+
+    #[test]
+    fn it_rejects_inconsistent_zones() {
+        assert!(zone("no-soa", "example.com", "www 60 A 192.0.2.1\n").is_err());
+        let two_soas = format!("{ZONE}@ SOA ns1 hostmaster 2 1 1 1 1\n");
+        assert!(zone("two-soas", "example.com", &two_soas).is_err());
+        let outside = format!("{ZONE}example.net. A 192.0.2.1\n");
+        assert!(zone("outside", "example.com", &outside).is_err());
+        let class = format!("{ZONE}www CH TXT \"x\"\n");
+        assert!(zone("class", "example.com", &class).is_err());
+        let cname = format!("{ZONE}www CNAME ns1\n");
+        assert!(zone("cname", "example.com", &cname).is_err());
+    }
+}
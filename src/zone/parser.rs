@@ -0,0 +1,674 @@
+use super::{Answer, DomainName, Rdata, RecordClass, RecordType, Result};
+use std::fs;
+use std::io::Cursor;
+use std::mem;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use std::slice;
+use std::str::FromStr;
+
+// `$INCLUDE`s nested any deeper are taken for a loop.
+const MAX_INCLUDE_DEPTH: usize = 8;
+// Ref: https://datatracker.ietf.org/doc/html/rfc1035#section-3.3
+const MAX_CHARACTER_STRING: usize = 255;
+const TCP: u8 = 6;
+const UDP: u8 = 17;
+
+// Records of a master file. Relative names are completed with `origin` until a `$ORIGIN`
+// entry sets another one.
+// Ref: https://datatracker.ietf.org/doc/html/rfc1035#section-5
+pub fn parse_file(path: &Path, origin: DomainName) -> Result<Vec<Answer>> {
+    let mut parser = Parser::new(origin);
+    parser.file(path, 0)?;
+    Ok(parser.records)
+}
+
+// What carries over from one entry to the next.
+#[derive(Debug)]
+struct Parser {
+    origin: DomainName,
+    // Set by `$TTL`. Ref: https://datatracker.ietf.org/doc/html/rfc2308#section-4
+    default_ttl: Option<u32>,
+    last_ttl: Option<u32>,
+    last_owner: Option<DomainName>,
+    last_class: RecordClass,
+    records: Vec<Answer>,
+}
+
+// One line of the file, or several joined by parentheses.
+#[derive(Debug)]
+struct Entry {
+    line: usize,
+    // Starts with a blank and so belongs to the owner of the previous record.
+    blank_owner: bool,
+    tokens: Vec<Token>,
+}
+
+// A word as written in the file, escapes included but without surrounding quotes.
+#[derive(Debug)]
+struct Token {
+    text: String,
+    quoted: bool,
+}
+
+impl Parser {
+    fn new(origin: DomainName) -> Self {
+        Self {
+            origin,
+            default_ttl: None,
+            last_ttl: None,
+            last_owner: None,
+            last_class: RecordClass::In,
+            records: vec![],
+        }
+    }
+
+    fn file(&mut self, path: &Path, depth: usize) -> Result<()> {
+        if depth > MAX_INCLUDE_DEPTH {
+            return Err(err!(
+                "$INCLUDE is nested more than {MAX_INCLUDE_DEPTH} deep"
+            ));
+        }
+
+        let text = fs::read_to_string(path).map_err(|e| err!("{}: {e}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        self.text(&text, dir, depth)
+            .map_err(|e| err!("{}: {e}", path.display()))
+    }
+
+    fn text(&mut self, text: &str, dir: &Path, depth: usize) -> Result<()> {
+        for entry in tokenize(text)? {
+            let line = entry.line;
+            self.entry(entry, dir, depth)
+                .map_err(|e| err!("line {line}: {e}"))?;
+        }
+
+        Ok(())
+    }
+
+    fn entry(&mut self, entry: Entry, dir: &Path, depth: usize) -> Result<()> {
+        let directive = match entry.tokens.first() {
+            Some(token) if !entry.blank_owner && !token.quoted && token.text.starts_with('$') => {
+                token.text.as_str()
+            }
+            _ => return self.record(entry),
+        };
+        let arg = |i: usize| {
+            entry
+                .tokens
+                .get(i)
+                .map(|token| token.text.as_str())
+                .ok_or(err!("{directive} is missing an argument"))
+        };
+
+        match directive {
+            "$ORIGIN" => self.origin = name(arg(1)?, &self.origin)?,
+            "$TTL" => self.default_ttl = Some(ttl(arg(1)?)?),
+            // The included file may start from another origin, but leaves ours unchanged.
+            "$INCLUDE" => {
+                let path = dir.join(arg(1)?);
+                let origin = match arg(2) {
+                    Ok(origin) => name(origin, &self.origin)?,
+                    Err(_) => self.origin.clone(),
+                };
+                let origin = mem::replace(&mut self.origin, origin);
+                let included = self.file(&path, depth + 1);
+                self.origin = origin;
+                included?;
+            }
+            other => return Err(err!("Unknown directive {other}")),
+        }
+
+        Ok(())
+    }
+
+    // <domain-name> [<TTL>] [<class>] <type> <RDATA>, where TTL and class may come in
+    // either order.
+    fn record(&mut self, entry: Entry) -> Result<()> {
+        let mut tokens = entry.tokens.iter();
+
+        let owner = if entry.blank_owner {
+            self.last_owner
+                .clone()
+                .ok_or(err!("First record has no owner"))?
+        } else {
+            let token = tokens.next().ok_or(err!("Record is empty"))?;
+            name(&token.text, &self.origin)?
+        };
+
+        let (mut explicit_ttl, mut class) = (None, None);
+        let r#type = loop {
+            let token = tokens.next().ok_or(err!("Record has no type"))?;
+
+            if explicit_ttl.is_none() && token.text.starts_with(|c: char| c.is_ascii_digit()) {
+                explicit_ttl = Some(ttl(&token.text)?);
+            } else if let (None, Ok(parsed)) = (class, token.text.parse::<RecordClass>()) {
+                class = Some(parsed);
+            } else {
+                break token.text.parse::<RecordType>()?;
+            }
+        };
+
+        if matches!(r#type, RecordType::Opt | RecordType::Any) {
+            return Err(err!("{type} records cannot appear in master files", type = r#type));
+        }
+
+        // Ref: https://datatracker.ietf.org/doc/html/rfc2308#section-4
+        let ttl = explicit_ttl
+            .or(self.default_ttl)
+            .or(self.last_ttl)
+            .ok_or(err!("Record has no TTL and no $TTL is set"))?;
+        let class = class.unwrap_or(self.last_class);
+        let data = rdata(r#type, tokens.as_slice(), &self.origin)?;
+
+        self.records
+            .push(Answer::record(owner.clone(), r#type, class, ttl, data));
+        self.last_owner = Some(owner);
+        self.last_class = class;
+        if explicit_ttl.is_some() {
+            self.last_ttl = explicit_ttl;
+        }
+
+        Ok(())
+    }
+}
+
+// Splits the text into entries, dropping comments and joining the lines of parenthesized
+// groups.
+fn tokenize(text: &str) -> Result<Vec<Entry>> {
+    let mut entries: Vec<Entry> = vec![];
+    let mut tokens: Vec<Token> = vec![];
+    let (mut line, mut entry_line, mut depth) = (1, 1, 0usize);
+    let (mut line_start, mut blank_owner) = (true, false);
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if line_start && depth == 0 {
+            entry_line = line;
+            blank_owner = c == ' ' || c == '\t';
+        }
+        line_start = false;
+
+        match c {
+            '\n' => {
+                line += 1;
+                line_start = true;
+
+                if depth == 0 && !tokens.is_empty() {
+                    entries.push(Entry {
+                        line: entry_line,
+                        blank_owner,
+                        tokens: mem::take(&mut tokens),
+                    });
+                }
+            }
+            ' ' | '\t' | '\r' => {}
+            ';' => while chars.next_if(|&c| c != '\n').is_some() {},
+            '(' => depth += 1,
+            ')' => {
+                depth = depth
+                    .checked_sub(1)
+                    .ok_or(err!("line {line}: ')' without '('"))?
+            }
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => {
+                            text.push('\\');
+                            text.extend(chars.next());
+                        }
+                        Some('\n') | None => {
+                            return Err(err!("line {line}: quoted string is not closed"))
+                        }
+                        Some(c) => text.push(c),
+                    }
+                }
+                tokens.push(Token { text, quoted: true });
+            }
+            c => {
+                let mut text = String::new();
+                let mut next = Some(c);
+                while let Some(c) = next {
+                    text.push(c);
+                    if c == '\\' {
+                        text.extend(chars.next());
+                    }
+                    next = chars.next_if(|c| {
+                        !matches!(c, ' ' | '\t' | '\r' | '\n' | ';' | '(' | ')' | '"')
+                    });
+                }
+                tokens.push(Token {
+                    text,
+                    quoted: false,
+                });
+            }
+        }
+    }
+
+    if depth > 0 {
+        return Err(err!("line {entry_line}: '(' is not closed"));
+    }
+
+    if !tokens.is_empty() {
+        entries.push(Entry {
+            line: entry_line,
+            blank_owner,
+            tokens,
+        });
+    }
+
+    Ok(entries)
+}
+
+// `@` stands for the origin, and a name without a trailing dot is relative to it.
+fn name(text: &str, origin: &DomainName) -> Result<DomainName> {
+    if text == "@" {
+        return Ok(origin.clone());
+    }
+
+    let name: DomainName = text.parse()?;
+    let escapes = text
+        .strip_suffix('.')
+        .map(|rest| rest.chars().rev().take_while(|&c| c == '\\').count());
+
+    match escapes {
+        Some(n) if n % 2 == 0 => Ok(name),
+        _ => DomainName::from_labels([name.labels(), origin.labels()].concat()),
+    }
+}
+
+// Seconds, also accepted with units as in `1h30m`.
+fn ttl(text: &str) -> Result<u32> {
+    let invalid = || err!("Invalid TTL {text}");
+    let (mut total, mut value) = (0u64, None::<u64>);
+
+    for c in text.chars() {
+        let unit = match c.to_ascii_lowercase() {
+            'w' => 604_800,
+            'd' => 86_400,
+            'h' => 3_600,
+            'm' => 60,
+            's' => 1,
+            c => {
+                let digit = c.to_digit(10).ok_or_else(invalid)? as u64;
+                value = value
+                    .unwrap_or(0)
+                    .checked_mul(10)
+                    .and_then(|v| v.checked_add(digit));
+                if value.is_none() {
+                    return Err(invalid());
+                }
+                continue;
+            }
+        };
+        let seconds = value.take().ok_or_else(invalid)?.checked_mul(unit);
+        total = seconds
+            .and_then(|seconds| total.checked_add(seconds))
+            .ok_or_else(invalid)?;
+    }
+
+    match value {
+        Some(seconds) => total = total.checked_add(seconds).ok_or_else(invalid)?,
+        None if text.is_empty() => return Err(invalid()),
+        None => {}
+    }
+
+    u32::try_from(total).map_err(|_| invalid())
+}
+
+// Ref: https://datatracker.ietf.org/doc/html/rfc1035#section-3.3
+//      https://datatracker.ietf.org/doc/html/rfc3597#section-5 (generic format)
+fn rdata(r#type: RecordType, tokens: &[Token], origin: &DomainName) -> Result<Rdata> {
+    if let Some((first, rest)) = tokens.split_first() {
+        if !first.quoted && first.text == "\\#" {
+            return generic_rdata(r#type, rest);
+        }
+    }
+
+    let mut fields = Fields {
+        tokens: tokens.iter(),
+        origin,
+    };
+
+    let data = match r#type {
+        RecordType::A => Rdata::A(fields.parse::<Ipv4Addr>()?.octets()),
+        RecordType::Ns => Rdata::Ns(fields.name()?),
+        RecordType::Md => Rdata::Md(fields.name()?),
+        RecordType::Mf => Rdata::Mf(fields.name()?),
+        RecordType::Cname => Rdata::Cname(fields.name()?),
+        RecordType::Soa => Rdata::Soa {
+            mname: fields.name()?,
+            rname: fields.name()?,
+            serial: fields.parse()?,
+            refresh: fields.ttl()?,
+            retry: fields.ttl()?,
+            expire: fields.ttl()?,
+            minimum: fields.ttl()?,
+        },
+        RecordType::Mb => Rdata::Mb(fields.name()?),
+        RecordType::Mg => Rdata::Mg(fields.name()?),
+        RecordType::Mr => Rdata::Mr(fields.name()?),
+        RecordType::Wks => {
+            let address = fields.parse::<Ipv4Addr>()?.octets();
+            let protocol = match fields.next()?.text.to_ascii_uppercase().as_str() {
+                "TCP" => TCP,
+                "UDP" => UDP,
+                other => other
+                    .parse()
+                    .map_err(|_| err!("Invalid WKS protocol {other}"))?,
+            };
+            let mut bitmap: Vec<u8> = vec![];
+            for token in fields.rest() {
+                let port: u16 = token
+                    .text
+                    .parse()
+                    .map_err(|_| err!("Invalid WKS port {}", token.text))?;
+                let (byte, bit) = (port as usize / 8, port % 8);
+                if bitmap.len() <= byte {
+                    bitmap.resize(byte + 1, 0);
+                }
+                bitmap[byte] |= 0b10000000 >> bit;
+            }
+            Rdata::Wks {
+                address,
+                protocol,
+                bitmap,
+            }
+        }
+        RecordType::Ptr => Rdata::Ptr(fields.name()?),
+        RecordType::Hinfo => Rdata::Hinfo {
+            cpu: fields.string()?,
+            os: fields.string()?,
+        },
+        RecordType::Minfo => Rdata::Minfo {
+            rmailbx: fields.name()?,
+            emailbx: fields.name()?,
+        },
+        RecordType::Mx => Rdata::Mx {
+            preference: fields.parse()?,
+            exchange: fields.name()?,
+        },
+        RecordType::Txt => {
+            let mut strings = vec![fields.string()?];
+            for token in fields.rest() {
+                strings.push(character_string(token)?);
+            }
+            Rdata::Txt(strings)
+        }
+        RecordType::Aaaa => Rdata::Aaaa(fields.parse::<Ipv6Addr>()?.octets()),
+        RecordType::Srv => Rdata::Srv {
+            priority: fields.parse()?,
+            weight: fields.parse()?,
+            port: fields.parse()?,
+            target: fields.name()?,
+        },
+        RecordType::Naptr => Rdata::Naptr {
+            order: fields.parse()?,
+            preference: fields.parse()?,
+            flags: fields.string()?,
+            services: fields.string()?,
+            regexp: fields.string()?,
+            replacement: fields.name()?,
+        },
+        RecordType::Sshfp => Rdata::Sshfp {
+            algorithm: fields.parse()?,
+            fp_type: fields.parse()?,
+            fingerprint: from_hex(&fields.rest().map(|t| t.text.as_str()).collect::<String>())?,
+        },
+        RecordType::Caa => Rdata::Caa {
+            flags: fields.parse()?,
+            tag: fields.string()?,
+            value: unescape(&fields.next()?.text)?,
+        },
+        RecordType::Null | RecordType::Opt | RecordType::Any | RecordType::Unknown(_) => {
+            return Err(err!("{type} records need the \\# format", type = r#type))
+        }
+    };
+
+    fields.end()?;
+    Ok(data)
+}
+
+// `\# <length> <hex>...`, decoded as if the bytes had come in a message.
+fn generic_rdata(r#type: RecordType, tokens: &[Token]) -> Result<Rdata> {
+    let (length, hex) = tokens.split_first().ok_or(err!("\\# has no length"))?;
+    let length: u16 = length
+        .text
+        .parse()
+        .map_err(|_| err!("Invalid RDATA length {}", length.text))?;
+    let bytes = from_hex(&hex.iter().map(|t| t.text.as_str()).collect::<String>())?;
+
+    if bytes.len() != length as usize {
+        return Err(err!("RDATA has {} bytes, not {length}", bytes.len()));
+    }
+
+    Rdata::new(&mut Cursor::new(&bytes[..]), r#type, length)
+}
+
+struct Fields<'a> {
+    tokens: slice::Iter<'a, Token>,
+    origin: &'a DomainName,
+}
+
+impl<'a> Fields<'a> {
+    fn next(&mut self) -> Result<&'a Token> {
+        self.tokens.next().ok_or(err!("RDATA is missing fields"))
+    }
+
+    fn parse<T: FromStr>(&mut self) -> Result<T> {
+        let token = self.next()?;
+        token
+            .text
+            .parse()
+            .map_err(|_| err!("Invalid RDATA field {}", token.text))
+    }
+
+    fn name(&mut self) -> Result<DomainName> {
+        name(&self.next()?.text, self.origin)
+    }
+
+    fn ttl(&mut self) -> Result<u32> {
+        ttl(&self.next()?.text)
+    }
+
+    fn string(&mut self) -> Result<Vec<u8>> {
+        character_string(self.next()?)
+    }
+
+    fn rest(&mut self) -> impl Iterator<Item = &'a Token> + '_ {
+        self.tokens.by_ref()
+    }
+
+    fn end(mut self) -> Result<()> {
+        match self.tokens.next() {
+            Some(token) => Err(err!("Unexpected RDATA field {}", token.text)),
+            None => Ok(()),
+        }
+    }
+}
+
+fn character_string(token: &Token) -> Result<Vec<u8>> {
+    let bytes = unescape(&token.text)?;
+
+    if bytes.len() > MAX_CHARACTER_STRING {
+        return Err(err!("String of {} bytes is longer than 255", bytes.len()));
+    }
+
+    Ok(bytes)
+}
+
+// `\DDD` is the byte with that decimal value, and a backslash before anything else keeps
+// that character as is.
+fn unescape(text: &str) -> Result<Vec<u8>> {
+    let mut bytes: Vec<u8> = vec![];
+    let mut iter = text.bytes();
+
+    while let Some(b) = iter.next() {
+        if b != b'\\' {
+            bytes.push(b);
+            continue;
+        }
+
+        match iter.next() {
+            Some(d) if d.is_ascii_digit() => {
+                let digits = [Some(d), iter.next(), iter.next()];
+                let value = digits.iter().try_fold(0u16, |acc, d| match d {
+                    Some(d) if d.is_ascii_digit() => Some(acc * 10 + (d - b'0') as u16),
+                    _ => None,
+                });
+                match value.and_then(|v| u8::try_from(v).ok()) {
+                    Some(byte) => bytes.push(byte),
+                    None => return Err(err!("Invalid escape in {text}")),
+                }
+            }
+            Some(c) => bytes.push(c),
+            None => return Err(err!("Invalid escape in {text}")),
+        }
+    }
+
+    Ok(bytes)
+}
+
+fn from_hex(text: &str) -> Result<Vec<u8>> {
+    if text.len() % 2 != 0 || !text.is_ascii() {
+        return Err(err!("Invalid hex {text}"));
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).map_err(|_| err!("Invalid hex {text}")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn parse(text: &str) -> Result<Vec<Answer>> {
+        let mut parser = Parser::new("example.com".parse().unwrap());
+        parser.text(text, Path::new("."), 0)?;
+        Ok(parser.records)
+    }
+
+    #[test]
+    fn it_parses_directives_and_relative_names() {
+        let records = parse(
+            "$TTL 1h
+@   IN  SOA ns1 hostmaster.example.com. (
+        2024010101 ; serial
+        2h 30m 1w
+        300 )
+    NS  ns1
+ns1 600 A 192.0.2.1 ; glue
+$ORIGIN sub
+www A 192.0.2.2
+",
+        )
+        .unwrap();
+
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0].name().to_string(), "example.com.");
+        assert_eq!(records[0].ttl(), 3600);
+        assert_eq!(
+            records[0].data().to_string(),
+            "ns1.example.com. hostmaster.example.com. 2024010101 7200 1800 604800 300"
+        );
+        assert_eq!(records[1].name().to_string(), "example.com.");
+        assert_eq!(records[1].data().to_string(), "ns1.example.com.");
+        assert_eq!(records[2].ttl(), 600);
+        assert_eq!(records[3].name().to_string(), "www.sub.example.com.");
+        assert_eq!(records[3].ttl(), 3600);
+        assert_eq!(records[3].class(), RecordClass::In);
+    }
+
+    #[test]
+    fn it_parses_rdata_of_every_type() {
+        let cases = [
+            ("A 192.0.2.1", "192.0.2.1"),
+            ("AAAA 2001:db8::1", "2001:db8::1"),
+            ("NS ns1.example.net.", "ns1.example.net."),
+            ("PTR host", "host.example.com."),
+            (
+                "MINFO admin errors",
+                "admin.example.com. errors.example.com.",
+            ),
+            ("MX 10 mail", "10 mail.example.com."),
+            (
+                r#"TXT "hello world" "say \"hi\"" plain \065"#,
+                r#""hello world" "say \"hi\"" "plain" "A""#,
+            ),
+            ("HINFO \"PC\" Linux", "\"PC\" \"Linux\""),
+            ("WKS 192.0.2.1 TCP 25 80", "192.0.2.1 6 25 80"),
+            ("SRV 0 5 5060 sip", "0 5 5060 sip.example.com."),
+            (
+                r#"NAPTR 100 10 "S" "SIP+D2U" "" _sip._udp"#,
+                r#"100 10 "S" "SIP+D2U" "" _sip._udp.example.com."#,
+            ),
+            ("SSHFP 1 1 dead BEEF", "1 1 deadbeef"),
+            ("CAA 0 issue \"ca.example\"", "0 issue \"ca.example\""),
+            ("TYPE65534 \\# 3 abcdef", "\\# 3 abcdef"),
+            ("NULL \\# 0", "\\# 0"),
+            ("A \\# 4 c0000201", "192.0.2.1"),
+        ];
+
+        for (rr, presentation) in cases {
+            let records = parse(&format!("a 60 IN {rr}")).unwrap();
+            assert_eq!(records[0].data().to_string(), presentation, "{rr}");
+        }
+    }
+
+    #[test]
+    fn it_rejects_malformed_entries() {
+        let long = format!("a 60 TXT \"{}\"", "x".repeat(256));
+        let cases = [
+            "a A 192.0.2.1",
+            "a 60 A 192.0.2.300",
+            "a 60 A 192.0.2.1 extra",
+            "a 60 MX 10",
+            "a 60 ( A 192.0.2.1",
+            "a 60 A 192.0.2.1 )",
+            "a 60 TXT \"open",
+            "a 60 OPT \\# 0",
+            "a 60 A \\# 4 c00002",
+            "a 60 BOGUS data",
+            "$BOGUS x",
+            "$TTL 99999999999999999w",
+            "$TTL 18446744073709551615s1",
+            "  60 A 192.0.2.1",
+            &long,
+        ];
+
+        for case in cases {
+            assert!(parse(case).is_err(), "{case}");
+        }
+
+        let err = parse("$TTL 60\n\na A nowhere").unwrap_err();
+        assert!(err.to_string().contains("line 3"), "{err}");
+    }
+
+    #[test]
+    fn it_includes_files_with_their_own_origin() {
+        let dir = env::temp_dir().join(format!("zone-include-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("main.zone"),
+            "$TTL 60\n$INCLUDE sub.zone sub\nwww A 192.0.2.1\n",
+        )
+        .unwrap();
+        fs::write(dir.join("sub.zone"), "www A 192.0.2.2\n").unwrap();
+        fs::write(dir.join("loop.zone"), "$INCLUDE loop.zone\n").unwrap();
+
+        let origin: DomainName = "example.com".parse().unwrap();
+        let records = parse_file(&dir.join("main.zone"), origin.clone()).unwrap();
+        let names: Vec<String> = records.iter().map(|r| r.name().to_string()).collect();
+        assert_eq!(names, ["www.sub.example.com.", "www.example.com."]);
+
+        assert!(parse_file(&dir.join("loop.zone"), origin.clone()).is_err());
+        assert!(parse_file(&dir.join("missing.zone"), origin).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}